@@ -0,0 +1,383 @@
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
+use crate::ports::{BoxError, CrawlerPort, MongoPort, PolicyStore, RedisPort};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Calls kept per port; the oldest are dropped first.
+const MAX_RECORDED_CALLS: usize = 10_000;
+
+/// Wraps a port so that calls can be recorded and made slow or failing,
+/// either at random per a [`FaultPlan`] or per a script of [`Fault`]s.
+/// Until something is switched on through `control`, calls go straight
+/// through.
+#[derive(Clone)]
+pub struct Chaos<P> {
+    pub inner: P,
+    pub control: ChaosControl,
+}
+
+impl<P> Chaos<P> {
+    /// `port` names the wrapped port in recorded calls and errors.
+    pub fn new(port: &'static str, inner: P) -> Self {
+        Self { inner, control: ChaosControl::new(port) }
+    }
+}
+
+/// What happens to a single call.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Wait, then run the call.
+    Delay(Duration),
+    /// Fail without running the call.
+    Error(String),
+    /// Wait, then fail without running the call, like a backend that stopped
+    /// answering.
+    Timeout(Duration),
+}
+
+/// Faults injected at random. Probabilities are per call, between 0 and 1.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultPlan {
+    /// Methods the plan applies to, e.g. `find_by_urls`; empty for all.
+    pub methods: Vec<String>,
+    pub latency_probability: f64,
+    pub latency_ms: u64,
+    pub error_probability: f64,
+    pub timeout_probability: f64,
+    pub timeout_ms: u64,
+    /// Makes the random choices repeatable.
+    pub seed: Option<u64>,
+    /// Keep the most recent calls for inspection.
+    pub record: bool,
+}
+
+impl FaultPlan {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, p) in [
+            ("latency_probability", self.latency_probability),
+            ("error_probability", self.error_probability),
+            ("timeout_probability", self.timeout_probability),
+        ] {
+            if !(0.0..=1.0).contains(&p) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, p));
+            }
+        }
+        Ok(())
+    }
+
+    fn applies_to(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m == method)
+    }
+}
+
+/// One call through a [`Chaos`] wrapper, with its arguments rendered as text.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecordedCall {
+    pub port: &'static str,
+    pub method: &'static str,
+    pub args: Vec<String>,
+}
+
+/// Shared switchboard of one wrapper; clones control the same wrapper.
+#[derive(Clone)]
+pub struct ChaosControl {
+    port: &'static str,
+    /// Whether anything is switched on; checked before taking the lock.
+    active: Arc<AtomicBool>,
+    state: Arc<Mutex<ChaosState>>,
+}
+
+struct ChaosState {
+    plan: FaultPlan,
+    rng: StdRng,
+    scripts: HashMap<String, VecDeque<Option<Fault>>>,
+    recording: bool,
+    calls: VecDeque<RecordedCall>,
+}
+
+impl ChaosControl {
+    pub fn new(port: &'static str) -> Self {
+        let state = ChaosState {
+            plan: FaultPlan::default(),
+            rng: StdRng::from_os_rng(),
+            scripts: HashMap::new(),
+            recording: false,
+            calls: VecDeque::new(),
+        };
+        Self { port, active: Arc::new(AtomicBool::new(false)), state: Arc::new(Mutex::new(state)) }
+    }
+
+    pub fn port(&self) -> &'static str {
+        self.port
+    }
+
+    pub fn plan(&self) -> FaultPlan {
+        self.lock().plan.clone()
+    }
+
+    /// Replaces the random faults. A default plan switches them off.
+    pub fn set_plan(&self, plan: FaultPlan) -> Result<(), String> {
+        plan.validate()?;
+        let mut state = self.lock();
+        state.rng = match plan.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        state.plan = plan;
+        self.refresh(&state);
+        Ok(())
+    }
+
+    /// Queues one step per upcoming call of `method`: `Some(fault)` applies
+    /// it, `None` lets the call through. Scripted calls skip the plan.
+    pub fn script(&self, method: &str, steps: impl IntoIterator<Item = Option<Fault>>) {
+        let mut state = self.lock();
+        state.scripts.entry(method.to_string()).or_default().extend(steps);
+        self.refresh(&state);
+    }
+
+    /// Records calls from now on, independent of the plan's `record` flag.
+    pub fn record(&self) {
+        let mut state = self.lock();
+        state.recording = true;
+        self.refresh(&state);
+    }
+
+    /// Switches everything off and forgets recorded calls.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.plan = FaultPlan::default();
+        state.scripts.clear();
+        state.recording = false;
+        state.calls.clear();
+        self.refresh(&state);
+    }
+
+    /// Recorded calls, oldest first.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.lock().calls.iter().cloned().collect()
+    }
+
+    /// Recorded calls of `method`.
+    pub fn calls_to(&self, method: &str) -> Vec<RecordedCall> {
+        self.lock().calls.iter().filter(|c| c.method == method).cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ChaosState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn refresh(&self, state: &ChaosState) {
+        let active = state.recording || state.plan != FaultPlan::default() || !state.scripts.is_empty();
+        self.active.store(active, Ordering::Relaxed);
+    }
+
+    /// Records the call if asked to and applies the fault chosen for it.
+    /// `Err` means the call must not reach the wrapped port.
+    async fn before(&self, method: &'static str, args: impl FnOnce() -> Vec<String>) -> Result<(), BoxError> {
+        if !self.active.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let faults = {
+            let mut state = self.lock();
+            if state.recording || state.plan.record {
+                if state.calls.len() >= MAX_RECORDED_CALLS {
+                    state.calls.pop_front();
+                }
+                state.calls.push_back(RecordedCall { port: self.port, method, args: args() });
+            }
+            state.choose(method)
+        };
+        for fault in faults {
+            match fault {
+                Fault::Delay(by) => tokio::time::sleep(by).await,
+                Fault::Error(message) => {
+                    return Err(format!("injected {} {} failure: {}", self.port, method, message).into());
+                }
+                Fault::Timeout(after) => {
+                    tokio::time::sleep(after).await;
+                    return Err(format!("injected {} {} timeout after {:?}", self.port, method, after).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChaosState {
+    fn choose(&mut self, method: &str) -> Vec<Fault> {
+        if let Some(script) = self.scripts.get_mut(method)
+            && let Some(step) = script.pop_front()
+        {
+            if script.is_empty() {
+                self.scripts.remove(method);
+            }
+            return step.into_iter().collect();
+        }
+        let plan = &self.plan;
+        if !plan.applies_to(method) {
+            return Vec::new();
+        }
+        let mut faults = Vec::new();
+        if self.rng.random::<f64>() < plan.latency_probability {
+            faults.push(Fault::Delay(Duration::from_millis(plan.latency_ms)));
+        }
+        let roll = self.rng.random::<f64>();
+        if roll < plan.timeout_probability {
+            faults.push(Fault::Timeout(Duration::from_millis(plan.timeout_ms)));
+        } else if roll < plan.timeout_probability + plan.error_probability {
+            faults.push(Fault::Error("random fault".to_string()));
+        }
+        faults
+    }
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+impl<R: RedisPort> RedisPort for Chaos<R> {
+    async fn multi_hgetall(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
+        self.control.before("multi_hgetall", || keys.to_vec()).await?;
+        self.inner.multi_hgetall(keys).await
+    }
+
+    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
+        self.control.before("hgetall", || vec![key.to_string()]).await?;
+        self.inner.hgetall(key).await
+    }
+
+    async fn write_cache_and_clear(
+        &self,
+        key: &str,
+        page: &StoredPage,
+        fetched_at: u64,
+        cache_ttl: u64,
+    ) -> Result<(), BoxError> {
+        self.control
+            .before("write_cache_and_clear", || vec![key.to_string(), fetched_at.to_string(), cache_ttl.to_string()])
+            .await?;
+        self.inner.write_cache_and_clear(key, page, fetched_at, cache_ttl).await
+    }
+
+    async fn set_inflight_fields(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+        inflight_ttl: u64,
+    ) -> Result<(), BoxError> {
+        self.control
+            .before("set_inflight_fields", || {
+                vec![key.to_string(), opt(last_mongo), opt(last_crawler), inflight_ttl.to_string()]
+            })
+            .await?;
+        self.inner.set_inflight_fields(key, last_mongo, last_crawler, inflight_ttl).await
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        self.control.before("mark_refresh", || vec![key.to_string(), opt(last_mongo), opt(last_crawler)]).await?;
+        self.inner.mark_refresh(key, last_mongo, last_crawler).await
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        self.control.before("get_failures", || keys.to_vec()).await?;
+        self.inner.get_failures(keys).await
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        self.control.before("set_failure", || vec![key.to_string(), ttl.to_string()]).await?;
+        self.inner.set_failure(key, failure, ttl).await
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        self.control.before("clear_failure", || vec![key.to_string()]).await?;
+        self.inner.clear_failure(key).await
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        self.control.before("remaining_ttl", || vec![key.to_string()]).await?;
+        self.inner.remaining_ttl(key).await
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        self.control.before("scan_prefix", || vec![prefix.to_string()]).await?;
+        self.inner.scan_prefix(prefix).await
+    }
+
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        self.control.before("scan_match", || vec![prefix.to_string(), pattern.to_string()]).await?;
+        self.inner.scan_match(prefix, pattern).await
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        self.control.before("delete_keys", || keys.to_vec()).await?;
+        self.inner.delete_keys(keys).await
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        self.control.before("ping", Vec::new).await?;
+        RedisPort::ping(&self.inner).await
+    }
+}
+
+impl<P: PolicyStore> PolicyStore for Chaos<P> {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        self.control.before("load_policies", || vec![key.to_string()]).await?;
+        self.inner.load_policies(key).await
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        self.control.before("save_policy", || vec![key.to_string(), rule.id.clone()]).await?;
+        self.inner.save_policy(key, rule).await
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        self.control.before("delete_policy", || vec![key.to_string(), id.to_string()]).await?;
+        self.inner.delete_policy(key, id).await
+    }
+}
+
+impl<M: MongoPort> MongoPort for Chaos<M> {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        self.control.before("find_by_urls", || urls.to_vec()).await?;
+        self.inner.find_by_urls(urls).await
+    }
+
+    async fn save_pages(&self, pages: &HashMap<String, StoredPage>) -> Result<(), BoxError> {
+        self.control.before("save_pages", || pages.keys().cloned().collect()).await?;
+        self.inner.save_pages(pages).await
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        self.control.before("ping", Vec::new).await?;
+        MongoPort::ping(&self.inner).await
+    }
+}
+
+impl<C: CrawlerPort> CrawlerPort for Chaos<C> {
+    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError> {
+        self.control.before("send_batch", || urls.to_vec()).await?;
+        self.inner.send_batch(urls).await
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        self.control.before("ping", Vec::new).await?;
+        CrawlerPort::ping(&self.inner).await
+    }
+}
@@ -0,0 +1,85 @@
+use crate::config::{CacheCompression, Config};
+use crate::metrics::CACHE_COMPRESSION_RATIO;
+use crate::ports::BoxError;
+use flate2::{Compression as GzipLevel, read::GzDecoder, write::GzEncoder};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Hash field naming the codec `data` was compressed with. Entries without it,
+/// including everything written before compression existed, hold plain text.
+pub const ENCODING_FIELD: &str = "data_encoding";
+
+#[derive(Clone, Copy, Debug)]
+pub struct PayloadCompression {
+    pub codec: CacheCompression,
+    /// Payloads shorter than this are stored as they are.
+    pub min_bytes: usize,
+}
+
+impl PayloadCompression {
+    pub fn disabled() -> Self {
+        Self { codec: CacheCompression::None, min_bytes: 0 }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self { codec: config.cache_compression, min_bytes: config.cache_compression_min_bytes }
+    }
+
+    /// Bytes to store for `data`, with the codec marker when they are
+    /// compressed. Payloads that do not shrink are kept uncompressed.
+    pub fn encode(&self, data: &str) -> Result<(Vec<u8>, Option<&'static str>), BoxError> {
+        if data.len() < self.min_bytes {
+            return Ok((data.as_bytes().to_vec(), None));
+        }
+        let (compressed, marker) = match self.codec {
+            CacheCompression::None => return Ok((data.as_bytes().to_vec(), None)),
+            CacheCompression::Zstd => (zstd::encode_all(data.as_bytes(), 0)?, "zstd"),
+            CacheCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzipLevel::default());
+                encoder.write_all(data.as_bytes())?;
+                (encoder.finish()?, "gzip")
+            }
+        };
+        if compressed.len() >= data.len() {
+            return Ok((data.as_bytes().to_vec(), None));
+        }
+        CACHE_COMPRESSION_RATIO.observe(data.len() as f64 / compressed.len() as f64);
+        Ok((compressed, Some(marker)))
+    }
+}
+
+/// Inverse of [`PayloadCompression::encode`] for the given marker.
+pub fn decode(encoding: Option<&str>, bytes: &[u8]) -> Result<String, BoxError> {
+    let raw = match encoding {
+        None => bytes.to_vec(),
+        Some("zstd") => zstd::decode_all(bytes)?,
+        Some("gzip") => {
+            let mut raw = Vec::new();
+            GzDecoder::new(bytes).read_to_end(&mut raw)?;
+            raw
+        }
+        Some(other) => return Err(format!("unknown payload encoding {:?}", other).into()),
+    };
+    Ok(String::from_utf8(raw)?)
+}
+
+/// Turns a hash read as raw bytes into the string form the service works
+/// with, decompressing `data`. An entry whose data cannot be decoded is
+/// returned without it, so it is treated as a cache miss.
+pub fn decode_hash(key: &str, mut raw: HashMap<String, Vec<u8>>) -> HashMap<String, String> {
+    let encoding = raw.remove(ENCODING_FIELD).map(|v| String::from_utf8_lossy(&v).into_owned());
+    let data = raw.remove("data");
+    let mut hash: HashMap<String, String> = raw
+        .into_iter()
+        .map(|(field, value)| (field, String::from_utf8_lossy(&value).into_owned()))
+        .collect();
+    if let Some(bytes) = data {
+        match decode(encoding.as_deref(), &bytes) {
+            Ok(data) => {
+                hash.insert("data".to_string(), data);
+            }
+            Err(e) => log::warn!("dropping undecodable cache data in {}: {}", key, e),
+        }
+    }
+    hash
+}
@@ -15,4 +15,11 @@ impl CrawlerPort for ReqwestCrawlerAdapter {
             Err(format!("crawler returned status {}", res.status()).into())
         }
     }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        // Any HTTP answer means the crawler is reachable; the crawl endpoint
+        // itself only accepts POST, so the status code is not checked here.
+        self.client.head(&self.url).send().await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,53 @@
+use crate::adapters::crawler_adapter::ReqwestCrawlerAdapter;
+#[cfg(feature = "kafka")]
+use crate::adapters::kafka_crawler_adapter::KafkaCrawlerAdapter;
+use crate::config::{Config, CrawlerMode};
+use crate::ports::{BoxError, CrawlerPort};
+
+/// The crawler dispatch picked by `crawler_mode`, forwarding every call like
+/// `RedisBackend` does for the Redis modes.
+#[derive(Clone)]
+pub enum CrawlerBackend {
+    Http(ReqwestCrawlerAdapter),
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaCrawlerAdapter),
+}
+
+impl CrawlerBackend {
+    pub fn from_config(config: &Config) -> Result<Self, BoxError> {
+        Ok(match config.crawler_mode {
+            CrawlerMode::Http => Self::Http(ReqwestCrawlerAdapter {
+                client: reqwest::Client::new(),
+                url: config.crawler_url.clone(),
+            }),
+            #[cfg(feature = "kafka")]
+            CrawlerMode::Kafka => Self::Kafka(KafkaCrawlerAdapter::new(
+                &config.kafka_brokers,
+                &config.kafka_topic,
+                std::time::Duration::from_millis(config.kafka_ack_timeout_ms),
+            )?),
+            #[cfg(not(feature = "kafka"))]
+            CrawlerMode::Kafka => return Err("crawler_mode = \"kafka\" needs a build with the `kafka` feature".into()),
+        })
+    }
+}
+
+macro_rules! forward {
+    ($self:ident, $adapter:ident => $call:expr) => {
+        match $self {
+            CrawlerBackend::Http($adapter) => $call.await,
+            #[cfg(feature = "kafka")]
+            CrawlerBackend::Kafka($adapter) => $call.await,
+        }
+    };
+}
+
+impl CrawlerPort for CrawlerBackend {
+    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError> {
+        forward!(self, a => a.send_batch(urls))
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        forward!(self, a => a.ping())
+    }
+}
@@ -0,0 +1,63 @@
+use crate::ports::{BoxError, CrawlerPort};
+use futures::future::try_join_all;
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use std::time::Duration;
+
+/// Publishes URLs to the topic the crawler fleet consumes, one message per
+/// URL with the URL as payload. Messages are keyed by host, so all URLs of a
+/// domain land in one partition and are consumed in order.
+#[derive(Clone)]
+pub struct KafkaCrawlerAdapter {
+    pub producer: FutureProducer,
+    pub topic: String,
+    /// How long a batch may wait for the brokers to acknowledge it.
+    pub ack_timeout: Duration,
+}
+
+impl KafkaCrawlerAdapter {
+    /// A producer for `brokers` (comma-separated `host:port`) that waits for
+    /// all in-sync replicas and does not reorder messages on retries.
+    pub fn new(brokers: &str, topic: &str, ack_timeout: Duration) -> Result<Self, BoxError> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", ack_timeout.as_millis().to_string())
+            .create()?;
+        Ok(Self { producer, topic: topic.to_string(), ack_timeout })
+    }
+}
+
+/// Partition key for `url`: its host, or the URL itself when it has none.
+pub fn message_key(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_else(|| url.to_string())
+}
+
+impl CrawlerPort for KafkaCrawlerAdapter {
+    /// Returns once every message is acknowledged; fails if any is not.
+    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError> {
+        let keys: Vec<String> = urls.iter().map(|url| message_key(url)).collect();
+        let deliveries = urls.iter().zip(&keys).map(|(url, key)| async move {
+            let record = FutureRecord::to(&self.topic).key(key).payload(url);
+            self.producer
+                .send(record, self.ack_timeout)
+                .await
+                .map_err(|(e, _)| BoxError::from(format!("publishing {} to {}: {}", url, self.topic, e)))
+        });
+        try_join_all(deliveries).await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        // Metadata requests block on the librdkafka side.
+        let producer = self.producer.clone();
+        let topic = self.topic.clone();
+        let timeout = self.ack_timeout;
+        tokio::task::spawn_blocking(move || producer.client().fetch_metadata(Some(&topic), timeout)).await??;
+        Ok(())
+    }
+}
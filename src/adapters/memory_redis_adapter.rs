@@ -0,0 +1,380 @@
+use crate::clock::{Clock, SystemClock};
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
+use crate::ports::{BoxError, PolicyStore, RedisPort};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// `RedisPort` kept in process memory, for single-node deployments without
+/// Redis and as a shared fake in tests. Keys expire like their Redis
+/// counterparts and the least recently used key is evicted once
+/// `max_entries` is reached. Clones share the same store.
+#[derive(Clone)]
+pub struct MemoryRedisAdapter {
+    state: Arc<Mutex<State>>,
+    /// Policy hashes by key, kept out of the LRU: they never expire, and
+    /// evicting one under load would drop every per-domain rule at once.
+    policies: Arc<Mutex<HashMap<String, HashMap<String, String>>>>,
+    clock: Arc<dyn Clock>,
+}
+
+enum Value {
+    Hash(HashMap<String, String>),
+    String(String),
+}
+
+struct Entry {
+    value: Value,
+    /// In ms since the epoch.
+    expires_at: Option<u64>,
+    /// Position in `State::recency`.
+    used: u64,
+}
+
+struct State {
+    entries: HashMap<String, Entry>,
+    /// Keys by last use, oldest first.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    max_entries: usize,
+}
+
+impl MemoryRedisAdapter {
+    pub fn new(max_entries: usize) -> Self {
+        let state = State { entries: HashMap::new(), recency: BTreeMap::new(), tick: 0, max_entries: max_entries.max(1) };
+        Self { state: Arc::new(Mutex::new(state)), policies: Arc::default(), clock: Arc::new(SystemClock) }
+    }
+
+    /// Expires keys by `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Number of live keys.
+    pub fn len(&self) -> usize {
+        let mut state = self.lock();
+        state.purge_expired(self.clock.now_ms());
+        state.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remaining time to live of `key`; `None` when it is missing or never expires.
+    pub fn ttl(&self, key: &str) -> Option<Duration> {
+        let now = self.clock.now_ms();
+        let mut state = self.lock();
+        state.live(key, now)?;
+        state.entries[key].expires_at.map(|at| Duration::from_millis(at.saturating_sub(now)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Every operation leaves the state consistent, so a panic elsewhere
+        // does not make it unusable.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_policies(&self) -> MutexGuard<'_, HashMap<String, HashMap<String, String>>> {
+        self.policies.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn read_hash(&self, key: &str) -> HashMap<String, String> {
+        let mut state = self.lock();
+        match state.live(key, self.clock.now_ms()).map(|e| &e.value) {
+            Some(Value::Hash(fields)) => fields.clone(),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// HSET: adds `fields` to the hash at `key`, creating it without a TTL.
+    fn set_fields(&self, key: &str, fields: Vec<(&str, String)>, ttl: Option<u64>) {
+        let now = self.clock.now_ms();
+        let mut state = self.lock();
+        if state.live(key, now).is_none() {
+            state.insert(key, Value::Hash(HashMap::new()), None, now);
+        }
+        let Some(entry) = state.entries.get_mut(key) else { return };
+        if !matches!(entry.value, Value::Hash(_)) {
+            entry.value = Value::Hash(HashMap::new());
+        }
+        if let Value::Hash(hash) = &mut entry.value {
+            hash.extend(fields.into_iter().map(|(f, v)| (f.to_string(), v)));
+        }
+        if let Some(ttl) = ttl {
+            entry.expires_at = Some(now + ttl * 1000);
+        }
+    }
+
+    fn remove(&self, key: &str) -> bool {
+        let mut state = self.lock();
+        let existed = state.live(key, self.clock.now_ms()).is_some();
+        state.remove(key);
+        existed
+    }
+}
+
+impl State {
+    /// The entry at `key` if it has not expired, marked as just used.
+    /// Expired entries are dropped on the way.
+    fn live(&mut self, key: &str, now: u64) -> Option<&mut Entry> {
+        let expired = self.entries.get(key)?.expires_at.is_some_and(|at| at <= now);
+        if expired {
+            self.remove(key);
+            return None;
+        }
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.used);
+        self.recency.insert(tick, key.to_string());
+        entry.used = tick;
+        Some(entry)
+    }
+
+    /// Replaces `key`, evicting the least recently used keys to make room.
+    fn insert(&mut self, key: &str, value: Value, expires_at: Option<u64>, now: u64) {
+        self.remove(key);
+        if self.entries.len() >= self.max_entries {
+            self.purge_expired(now);
+        }
+        while self.entries.len() >= self.max_entries {
+            let Some((_, oldest)) = self.recency.pop_first() else { break };
+            log::debug!("memory store full, evicting {}", oldest);
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key.to_string());
+        self.entries.insert(key.to_string(), Entry { value, expires_at, used: self.tick });
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+
+    fn purge_expired(&mut self, now: u64) {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.expires_at.is_some_and(|at| at <= now))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in expired {
+            self.remove(&key);
+        }
+    }
+}
+
+/// Whether `text` matches the Redis glob `pattern`, as SCAN MATCH decides.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`: its pattern position and the text
+    // position it has consumed up to so far.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some(next) = glob_match_one(pattern, p, text[t]) {
+            p = next;
+            t += 1;
+        } else if let Some((after_star, consumed)) = star {
+            star = Some((after_star, consumed + 1));
+            p = after_star;
+            t = consumed + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the pattern element at `p`, returning the position
+/// of the next element.
+fn glob_match_one(pattern: &[char], p: usize, c: char) -> Option<usize> {
+    match *pattern.get(p)? {
+        '?' => Some(p + 1),
+        '\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        '[' => {
+            let mut i = p + 1;
+            let negate = pattern.get(i) == Some(&'^');
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            while i < pattern.len() && pattern[i] != ']' {
+                if pattern[i] == '\\' && i + 1 < pattern.len() {
+                    matched |= pattern[i + 1] == c;
+                    i += 2;
+                } else if i + 2 < pattern.len() && pattern[i + 1] == '-' {
+                    let (low, high) = (pattern[i].min(pattern[i + 2]), pattern[i].max(pattern[i + 2]));
+                    matched |= (low..=high).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= pattern[i] == c;
+                    i += 1;
+                }
+            }
+            // like Redis, a class left open runs to the end of the pattern
+            (matched != negate).then_some((i + 1).min(pattern.len()))
+        }
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+fn inflight_fields(last_mongo: Option<u64>, last_crawler: Option<u64>) -> Vec<(&'static str, String)> {
+    let mut fields = Vec::new();
+    if let Some(m) = last_mongo {
+        fields.push(("last_mongo_fetch", m.to_string()));
+    }
+    if let Some(c) = last_crawler {
+        fields.push(("last_crawler_send", c.to_string()));
+    }
+    fields
+}
+
+impl RedisPort for MemoryRedisAdapter {
+    async fn multi_hgetall(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
+        Ok(keys.iter().map(|key| self.read_hash(key)).collect())
+    }
+
+    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
+        Ok(self.read_hash(key))
+    }
+
+    async fn write_cache_and_clear(
+        &self,
+        key: &str,
+        page: &StoredPage,
+        fetched_at: u64,
+        cache_ttl: u64,
+    ) -> Result<(), BoxError> {
+        let mut fields: HashMap<String, String> =
+            page.to_cache_fields().into_iter().map(|(f, v)| (f.to_string(), v)).collect();
+        fields.insert("fetched_at".to_string(), fetched_at.to_string());
+        let now = self.clock.now_ms();
+        self.lock().insert(key, Value::Hash(fields), Some(now + cache_ttl * 1000), now);
+        Ok(())
+    }
+
+    async fn set_inflight_fields(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+        inflight_ttl: u64,
+    ) -> Result<(), BoxError> {
+        let fields = inflight_fields(last_mongo, last_crawler);
+        if !fields.is_empty() {
+            self.set_fields(key, fields, Some(inflight_ttl));
+        }
+        Ok(())
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        let fields = inflight_fields(last_mongo, last_crawler);
+        if !fields.is_empty() {
+            self.set_fields(key, fields, None);
+        }
+        Ok(())
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        let now = self.clock.now_ms();
+        let mut state = self.lock();
+        let failures = keys
+            .iter()
+            .map(|key| match state.live(key, now).map(|e| &e.value) {
+                Some(Value::String(json)) => serde_json::from_str(json)
+                    .map_err(|e| log::warn!("ignoring unreadable failure record {}: {}", key, e))
+                    .ok(),
+                _ => None,
+            })
+            .collect();
+        Ok(failures)
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        let json = serde_json::to_string(failure)?;
+        let now = self.clock.now_ms();
+        self.lock().insert(key, Value::String(json), Some(now + ttl * 1000), now);
+        Ok(())
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        Ok(self.remove(key))
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        Ok(self.ttl(key))
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        let mut state = self.lock();
+        state.purge_expired(self.clock.now_ms());
+        let mut keys: Vec<String> = state.entries.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let mut state = self.lock();
+        state.purge_expired(self.clock.now_ms());
+        let mut keys: Vec<String> = state
+            .entries
+            .keys()
+            .filter(|key| {
+                key.strip_prefix(prefix).is_some_and(|rest| glob_match(&pattern, &rest.chars().collect::<Vec<_>>()))
+            })
+            .cloned()
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        Ok(keys.iter().filter(|key| self.remove(key)).count() as u64)
+    }
+
+    /// Always reachable: the store lives in this process.
+    async fn ping(&self) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+impl PolicyStore for MemoryRedisAdapter {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        let mut rules = Vec::new();
+        let stored = self.lock_policies().get(key).cloned().unwrap_or_default();
+        for (id, json) in stored {
+            match serde_json::from_str::<PolicyRule>(&json) {
+                Ok(rule) => rules.push(rule),
+                Err(e) => log::warn!("ignoring unreadable policy {:?} in {}: {}", id, key, e),
+            }
+        }
+        Ok(rules)
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        let json = serde_json::to_string(rule)?;
+        self.lock_policies().entry(key.to_string()).or_default().insert(rule.id.clone(), json);
+        Ok(())
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        Ok(self.lock_policies().get_mut(key).is_some_and(|hash| hash.remove(id).is_some()))
+    }
+}
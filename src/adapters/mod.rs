@@ -1,5 +1,16 @@
 pub mod redis_adapter;
+pub mod redis_cluster_adapter;
+pub mod redis_sentinel_adapter;
+pub mod memory_redis_adapter;
+pub mod redis_backend;
 pub mod mongo_adapter;
+pub mod sqlite_adapter;
+pub mod storage_backend;
 pub mod crawler_adapter;
+#[cfg(feature = "kafka")]
+pub mod kafka_crawler_adapter;
+pub mod crawler_backend;
+pub mod compression;
+pub mod chaos;
 
 // re-exports removed to avoid unused import warnings; use fully-qualified paths where needed.
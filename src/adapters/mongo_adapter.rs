@@ -1,28 +1,268 @@
+use crate::config::{Config, MongoIndex};
+use crate::domain::{PageMeta, StoredPage};
 use crate::ports::{BoxError, MongoPort};
-use futures::stream::TryStreamExt;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use mongodb::{
-    Collection,
-    bson::{Document, doc},
+    Collection, IndexModel,
+    bson::{self, Bson, Document, doc, oid::ObjectId},
+    error::ErrorKind,
+    options::IndexOptions,
 };
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct MongoAdapter {
     pub coll: Collection<Document>,
+    pub query: QueryOptions,
+}
+
+/// How `find_by_urls` splits large lookups.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryOptions {
+    /// URLs per `$in` query.
+    pub chunk_size: usize,
+    /// Queries in flight at once for one lookup.
+    pub concurrency: usize,
+    /// Server-side limit per query (`maxTimeMS`); `None` for no limit.
+    pub max_time: Option<Duration>,
+}
+
+impl QueryOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            chunk_size: config.mongo_query_chunk_size,
+            concurrency: config.mongo_query_concurrency,
+            max_time: (config.mongo_max_time_ms > 0).then(|| Duration::from_millis(config.mongo_max_time_ms)),
+        }
+    }
+
+    /// The distinct `urls`, in order, split into one list per query.
+    pub fn chunks(&self, urls: &[String]) -> Vec<Vec<String>> {
+        let mut seen = HashSet::new();
+        let distinct: Vec<String> = urls.iter().filter(|u| seen.insert(*u)).cloned().collect();
+        distinct.chunks(self.chunk_size.max(1)).map(<[String]>::to_vec).collect()
+    }
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self { chunk_size: 1_000, concurrency: 4, max_time: Some(Duration::from_millis(5_000)) }
+    }
+}
+
+/// Fields `PageDocument` reads; the rest of a document stays on the server.
+const PAGE_FIELDS: [&str; 7] = ["url", "data", "status_code", "content_type", "final_url", "crawled_at", "etag"];
+
+impl MongoAdapter {
+    /// Checks that the unique `url` index and `extra` exist and creates the
+    /// missing ones. Failing to create one, e.g. a unique index over existing
+    /// duplicates, is logged and does not stop the others. Returns the names
+    /// of the indexes created.
+    pub async fn ensure_indexes(&self, extra: &[MongoIndex]) -> Result<Vec<String>, BoxError> {
+        let existing: Vec<IndexModel> = match self.coll.list_indexes().await {
+            Ok(cursor) => cursor.try_collect().await?,
+            // the collection does not exist yet
+            Err(e) if matches!(*e.kind, ErrorKind::Command(ref c) if c.code == 26) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let wanted = std::iter::once(MongoIndex::unique_url()).chain(extra.iter().cloned());
+        let mut created = Vec::new();
+        for spec in wanted {
+            let keys = spec.keys();
+            if let Some(found) = existing.iter().find(|m| key_pattern(&m.keys) == keys) {
+                let unique = found.options.as_ref().and_then(|o| o.unique).unwrap_or(false);
+                if unique != spec.unique {
+                    log::warn!(
+                        "index on {:?} exists with unique={} but unique={} is configured; leaving it as is",
+                        spec.fields, unique, spec.unique
+                    );
+                }
+                continue;
+            }
+            let mut key_doc = Document::new();
+            for (field, direction) in &keys {
+                key_doc.insert(*field, *direction);
+            }
+            let options = IndexOptions::builder().name(spec.name.clone()).unique(spec.unique).build();
+            let model = IndexModel::builder().keys(key_doc).options(options).build();
+            match self.coll.create_index(model).await {
+                Ok(result) => {
+                    log::info!("created index {} on {:?}", result.index_name, spec.fields);
+                    created.push(result.index_name);
+                }
+                Err(e) => log::error!("cannot create index on {:?}: {}", spec.fields, e),
+            }
+        }
+        Ok(created)
+    }
+
+    async fn find_chunk(&self, urls: Vec<String>) -> Result<Vec<Document>, BoxError> {
+        let filter = doc! { "url": { "$in": urls } };
+        let projection: Document = PAGE_FIELDS.iter().map(|f| (f.to_string(), Bson::Int32(1))).collect();
+        let mut find = self.coll.find(filter).projection(projection);
+        if let Some(max_time) = self.query.max_time {
+            find = find.max_time(max_time);
+        }
+        Ok(find.await?.try_collect().await?)
+    }
 }
 
 impl MongoPort for MongoAdapter {
-    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, String>, BoxError> {
-        let filter = doc! { "url": { "$in": urls.iter().cloned().collect::<Vec<_>>() } };
-        let mut cursor = self.coll.find(filter).await?;
-        let mut map = HashMap::new();
-        while let Some(doc) = cursor.try_next().await? {
-            if let Ok(u) = doc.get_str("url") {
-                if let Ok(d) = doc.get_str("data") {
-                    map.insert(u.to_string(), d.to_string());
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        let chunks: Vec<Vec<Document>> = stream::iter(self.query.chunks(urls))
+            .map(|chunk| self.find_chunk(chunk))
+            .buffer_unordered(self.query.concurrency.max(1))
+            .try_collect()
+            .await?;
+        let mut newest = NewestPages::default();
+        for doc in chunks.into_iter().flatten() {
+            match bson::from_document::<PageDocument>(doc) {
+                Ok(page) => {
+                    newest.insert(page);
+                }
+                Err(e) => log::warn!("skipping unreadable page document: {}", e),
+            }
+        }
+        Ok(newest.into_pages())
+    }
+
+    async fn save_pages(&self, pages: &HashMap<String, StoredPage>) -> Result<(), BoxError> {
+        for (url, page) in pages {
+            let meta = &page.meta;
+            let values = [
+                ("status_code", meta.status_code.map(|c| Bson::Int32(i32::from(c)))),
+                ("content_type", meta.content_type.clone().map(Bson::String)),
+                ("final_url", meta.final_url.clone().map(Bson::String)),
+                ("crawled_at", meta.crawled_at.map(|at| Bson::DateTime(bson::DateTime::from_millis(at as i64)))),
+                ("etag", meta.etag.clone().map(Bson::String)),
+            ];
+            // Fields the crawler added beyond PAGE_FIELDS are left alone.
+            let mut set = doc! { "data": bson::to_bson(&page.data)? };
+            let mut unset = Document::new();
+            for (field, value) in values {
+                match value {
+                    Some(value) => set.insert(field, value),
+                    None => unset.insert(field, ""),
+                };
+            }
+            let mut update = doc! { "$set": set };
+            if !unset.is_empty() {
+                update.insert("$unset", unset);
+            }
+            self.coll.update_one(doc! { "url": url }, update).upsert(true).await?;
+        }
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        let db = self.coll.client().database(&self.coll.namespace().db);
+        db.run_command(doc! { "ping": 1 }).await?;
+        Ok(())
+    }
+}
+
+/// A crawled page as written to the collection by the crawler. Only `url`
+/// and `data` are required; `data` may be any BSON value.
+#[derive(Deserialize, Debug)]
+pub struct PageDocument {
+    pub url: String,
+    pub data: Bson,
+    #[serde(default)]
+    pub status_code: Option<u16>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub final_url: Option<String>,
+    /// A BSON date or a number of ms since the epoch.
+    #[serde(default)]
+    pub crawled_at: Option<Bson>,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default, rename = "_id")]
+    pub id: Option<Bson>,
+}
+
+impl PageDocument {
+    /// Orders duplicates of a URL: the latest crawl wins, then the most
+    /// recently inserted document when `_id` is an ObjectId. Other ids only
+    /// break ties, by their canonical extended JSON, so that the order is
+    /// total.
+    fn recency(&self) -> (Option<u64>, Option<ObjectId>, Option<String>) {
+        let crawled_at = self.crawled_at.as_ref().and_then(bson_millis);
+        let oid = match &self.id {
+            Some(Bson::ObjectId(oid)) => Some(*oid),
+            _ => None,
+        };
+        let id = self.id.clone().map(|id| id.into_canonical_extjson().to_string());
+        (crawled_at, oid, id)
+    }
+
+    pub fn into_page(self) -> (String, StoredPage) {
+        let meta = PageMeta {
+            status_code: self.status_code,
+            content_type: self.content_type,
+            final_url: self.final_url,
+            crawled_at: self.crawled_at.as_ref().and_then(bson_millis),
+            etag: self.etag,
+        };
+        // Relaxed extended JSON keeps numbers and strings as they are and
+        // spells out dates, ids and decimals as `$date`, `$oid`, ... objects.
+        (self.url, StoredPage::new(self.data.into_relaxed_extjson()).with_meta(meta))
+    }
+}
+
+/// Keeps one document per URL regardless of the order the cursor yields
+/// duplicates in.
+#[derive(Default)]
+pub struct NewestPages {
+    pages: HashMap<String, PageDocument>,
+}
+
+impl NewestPages {
+    pub fn insert(&mut self, page: PageDocument) {
+        match self.pages.entry(page.url.clone()) {
+            Entry::Occupied(mut kept) => {
+                if page.recency() > kept.get().recency() {
+                    log::debug!("duplicate documents for {}, keeping the newest", kept.key());
+                    kept.insert(page);
                 }
             }
+            Entry::Vacant(slot) => {
+                slot.insert(page);
+            }
         }
-        Ok(map)
+    }
+
+    pub fn into_pages(self) -> HashMap<String, StoredPage> {
+        self.pages.into_values().map(PageDocument::into_page).collect()
+    }
+}
+
+/// Index key pattern as `(field, 1 | -1)` pairs; special index types such
+/// as `text` or `hashed` map to 0 so they never match a configured index.
+fn key_pattern(keys: &Document) -> Vec<(&str, i32)> {
+    keys.iter()
+        .map(|(field, value)| {
+            let direction = match value {
+                Bson::Int32(n) => n.signum(),
+                Bson::Int64(n) => n.signum() as i32,
+                Bson::Double(n) if *n != 0.0 => n.signum() as i32,
+                _ => 0,
+            };
+            (field.as_str(), direction)
+        })
+        .collect()
+}
+
+fn bson_millis(value: &Bson) -> Option<u64> {
+    match value {
+        Bson::DateTime(dt) => u64::try_from(dt.timestamp_millis()).ok(),
+        Bson::Int64(ms) => u64::try_from(*ms).ok(),
+        Bson::Int32(ms) => u64::try_from(*ms).ok(),
+        Bson::Double(ms) if *ms >= 0.0 => Some(*ms as u64),
+        _ => None,
     }
 }
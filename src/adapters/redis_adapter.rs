@@ -1,14 +1,20 @@
+use crate::adapters::compression::{self, ENCODING_FIELD, PayloadCompression};
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
 use crate::ports::BoxError;
-use crate::ports::RedisPort;
+use crate::ports::{PolicyStore, RedisPort};
 use deadpool_redis::{
     Pool,
-    redis::{cmd, pipe},
+    redis::{aio::ConnectionLike, cmd, pipe},
 };
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct DeadpoolRedisAdapter {
     pub pool: Pool,
+    /// Applied to `data` on write; reads decode whatever codec an entry has.
+    pub compression: PayloadCompression,
 }
 
 impl RedisPort for DeadpoolRedisAdapter {
@@ -17,34 +23,23 @@ impl RedisPort for DeadpoolRedisAdapter {
         keys: &[String],
     ) -> Result<Vec<HashMap<String, String>>, BoxError> {
         let mut conn = self.pool.get().await?;
-        let mut rpipe = pipe();
-        for key in keys {
-            rpipe.cmd("HGETALL").arg(key);
-        }
-        let hashes: Vec<HashMap<String, String>> = rpipe.query_async(&mut conn).await?;
-        Ok(hashes)
+        multi_hgetall(&mut conn, keys).await
     }
 
     async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
         let mut conn = self.pool.get().await?;
-        let hash: HashMap<String, String> = cmd("HGETALL").arg(key).query_async(&mut conn).await?;
-        Ok(hash)
+        hgetall(&mut conn, key).await
     }
 
     async fn write_cache_and_clear(
         &self,
         key: &str,
-        data: &str,
+        page: &StoredPage,
+        fetched_at: u64,
         cache_ttl: u64,
     ) -> Result<(), BoxError> {
         let mut conn = self.pool.get().await?;
-        let mut rpipe = pipe();
-        rpipe.cmd("HSET").arg(key).arg("data").arg(data);
-        rpipe.cmd("HDEL").arg(key).arg("last_mongo_fetch");
-        rpipe.cmd("HDEL").arg(key).arg("last_crawler_send");
-        rpipe.cmd("EXPIRE").arg(key).arg(cache_ttl);
-        let _: () = rpipe.query_async(&mut conn).await?;
-        Ok(())
+        write_cache_and_clear(&mut conn, &self.compression, key, page, fetched_at, cache_ttl).await
     }
 
     async fn set_inflight_fields(
@@ -55,25 +50,316 @@ impl RedisPort for DeadpoolRedisAdapter {
         inflight_ttl: u64,
     ) -> Result<(), BoxError> {
         let mut conn = self.pool.get().await?;
-        let mut pipe_cmd = pipe();
-        if let Some(m) = last_mongo {
-            pipe_cmd
-                .cmd("HSET")
-                .arg(key)
-                .arg("last_mongo_fetch")
-                .arg(m.to_string());
+        set_inflight_fields(&mut conn, key, last_mongo, last_crawler, inflight_ttl).await
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        mark_refresh(&mut conn, key, last_mongo, last_crawler).await
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
         }
-        if let Some(c) = last_crawler {
-            pipe_cmd
-                .cmd("HSET")
-                .arg(key)
-                .arg("last_crawler_send")
-                .arg(c.to_string());
+        let mut conn = self.pool.get().await?;
+        get_failures(&mut conn, keys).await
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        set_failure(&mut conn, key, failure, ttl).await
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        let mut conn = self.pool.get().await?;
+        delete(&mut conn, key).await
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        remaining_ttl(&mut conn, key).await
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        scan_prefix(&mut conn, prefix).await
+    }
+
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        scan_match(&mut conn, prefix, pattern).await
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        let mut conn = self.pool.get().await?;
+        delete_keys(&mut conn, keys).await
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        ping(&mut conn).await
+    }
+}
+
+impl PolicyStore for DeadpoolRedisAdapter {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        load_policies(&mut conn, key).await
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        save_policy(&mut conn, key, rule).await
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        let mut conn = self.pool.get().await?;
+        delete_policy(&mut conn, key, id).await
+    }
+}
+
+// The commands behind each port method, shared by every connection mode.
+// Multi-key helpers expect all keys to be served by the connection, which on
+// a cluster means the same slot.
+
+pub(crate) async fn multi_hgetall<C: ConnectionLike + Send>(
+    conn: &mut C,
+    keys: &[String],
+) -> Result<Vec<HashMap<String, String>>, BoxError> {
+    let mut rpipe = pipe();
+    for key in keys {
+        rpipe.cmd("HGETALL").arg(key);
+    }
+    let raw: Vec<HashMap<String, Vec<u8>>> = rpipe.query_async(conn).await?;
+    Ok(keys.iter().zip(raw).map(|(key, hash)| compression::decode_hash(key, hash)).collect())
+}
+
+pub(crate) async fn hgetall<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+) -> Result<HashMap<String, String>, BoxError> {
+    let raw: HashMap<String, Vec<u8>> = cmd("HGETALL").arg(key).query_async(conn).await?;
+    Ok(compression::decode_hash(key, raw))
+}
+
+pub(crate) async fn write_cache_and_clear<C: ConnectionLike + Send>(
+    conn: &mut C,
+    compression: &PayloadCompression,
+    key: &str,
+    page: &StoredPage,
+    fetched_at: u64,
+    cache_ttl: u64,
+) -> Result<(), BoxError> {
+    let mut rpipe = pipe();
+    rpipe.atomic();
+    rpipe.cmd("DEL").arg(key).ignore();
+    let hset = rpipe.cmd("HSET").arg(key);
+    for (field, value) in page.to_cache_fields() {
+        if field == "data" {
+            let (bytes, encoding) = compression.encode(&value)?;
+            hset.arg(field).arg(bytes);
+            if let Some(encoding) = encoding {
+                hset.arg(ENCODING_FIELD).arg(encoding);
+            }
+        } else {
+            hset.arg(field).arg(value);
+        }
+    }
+    hset.arg("fetched_at").arg(fetched_at.to_string()).ignore();
+    rpipe.cmd("EXPIRE").arg(key).arg(cache_ttl).ignore();
+    let _: () = rpipe.query_async(conn).await?;
+    Ok(())
+}
+
+pub(crate) async fn set_inflight_fields<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    last_mongo: Option<u64>,
+    last_crawler: Option<u64>,
+    inflight_ttl: u64,
+) -> Result<(), BoxError> {
+    let mut pipe_cmd = pipe();
+    if let Some(m) = last_mongo {
+        pipe_cmd
+            .cmd("HSET")
+            .arg(key)
+            .arg("last_mongo_fetch")
+            .arg(m.to_string());
+    }
+    if let Some(c) = last_crawler {
+        pipe_cmd
+            .cmd("HSET")
+            .arg(key)
+            .arg("last_crawler_send")
+            .arg(c.to_string());
+    }
+    if last_mongo.is_some() || last_crawler.is_some() {
+        pipe_cmd.cmd("EXPIRE").arg(key).arg(inflight_ttl);
+        let _: () = pipe_cmd.query_async(conn).await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn mark_refresh<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    last_mongo: Option<u64>,
+    last_crawler: Option<u64>,
+) -> Result<(), BoxError> {
+    let mut hset = cmd("HSET");
+    hset.arg(key);
+    if let Some(m) = last_mongo {
+        hset.arg("last_mongo_fetch").arg(m.to_string());
+    }
+    if let Some(c) = last_crawler {
+        hset.arg("last_crawler_send").arg(c.to_string());
+    }
+    if last_mongo.is_some() || last_crawler.is_some() {
+        let _: () = hset.query_async(conn).await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_failures<C: ConnectionLike + Send>(
+    conn: &mut C,
+    keys: &[String],
+) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+    let raw: Vec<Option<String>> = cmd("MGET").arg(keys).query_async(conn).await?;
+    let failures = raw
+        .into_iter()
+        .zip(keys)
+        .map(|(json, key)| {
+            let json = json?;
+            serde_json::from_str(&json)
+                .map_err(|e| log::warn!("ignoring unreadable failure record {}: {}", key, e))
+                .ok()
+        })
+        .collect();
+    Ok(failures)
+}
+
+pub(crate) async fn set_failure<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    failure: &UrlFailure,
+    ttl: u64,
+) -> Result<(), BoxError> {
+    let json = serde_json::to_string(failure)?;
+    let _: () = cmd("SET").arg(key).arg(json).arg("EX").arg(ttl).query_async(conn).await?;
+    Ok(())
+}
+
+/// Returns whether the key existed.
+pub(crate) async fn delete<C: ConnectionLike + Send>(conn: &mut C, key: &str) -> Result<bool, BoxError> {
+    let removed: u64 = cmd("DEL").arg(key).query_async(conn).await?;
+    Ok(removed > 0)
+}
+
+pub(crate) async fn remaining_ttl<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+) -> Result<Option<Duration>, BoxError> {
+    // Negative replies mean the key is missing (-2) or has no expiry (-1).
+    let ms: i64 = cmd("PTTL").arg(key).query_async(conn).await?;
+    Ok(u64::try_from(ms).ok().map(Duration::from_millis))
+}
+
+/// Keys per SCAN round trip; a hint to the server, not a limit.
+pub(crate) const SCAN_COUNT: u64 = 1_000;
+
+/// Glob pattern for keys made of the literal `prefix` followed by text
+/// matching `pattern`: glob syntax is escaped in the prefix only.
+pub(crate) fn match_pattern(prefix: &str, pattern: &str) -> String {
+    let mut glob = String::with_capacity(prefix.len() + pattern.len());
+    for c in prefix.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            glob.push('\\');
         }
-        if last_mongo.is_some() || last_crawler.is_some() {
-            pipe_cmd.cmd("EXPIRE").arg(key).arg(inflight_ttl);
-            let _: () = pipe_cmd.query_async(&mut conn).await?;
+        glob.push(c);
+    }
+    glob.push_str(pattern);
+    glob
+}
+
+/// Keys starting with `prefix` on the node behind `conn`.
+pub(crate) async fn scan_prefix<C: ConnectionLike + Send>(conn: &mut C, prefix: &str) -> Result<Vec<String>, BoxError> {
+    scan_match(conn, prefix, "*").await
+}
+
+/// Keys on the node behind `conn` made of `prefix` and text matching
+/// `pattern`. SCAN may return a key more than once; the result has each key
+/// once.
+pub(crate) async fn scan_match<C: ConnectionLike + Send>(
+    conn: &mut C,
+    prefix: &str,
+    pattern: &str,
+) -> Result<Vec<String>, BoxError> {
+    let pattern = match_pattern(prefix, pattern);
+    let mut keys = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next, batch): (u64, Vec<String>) =
+            cmd("SCAN").arg(cursor).arg("MATCH").arg(&pattern).arg("COUNT").arg(SCAN_COUNT).query_async(conn).await?;
+        keys.extend(batch);
+        if next == 0 {
+            break;
         }
-        Ok(())
+        cursor = next;
     }
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+/// Returns how many of `keys` existed.
+pub(crate) async fn delete_keys<C: ConnectionLike + Send>(conn: &mut C, keys: &[String]) -> Result<u64, BoxError> {
+    if keys.is_empty() {
+        return Ok(0);
+    }
+    Ok(cmd("DEL").arg(keys).query_async(conn).await?)
+}
+
+pub(crate) async fn ping<C: ConnectionLike + Send>(conn: &mut C) -> Result<(), BoxError> {
+    let _: String = cmd("PING").query_async(conn).await?;
+    Ok(())
+}
+
+pub(crate) async fn load_policies<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+) -> Result<Vec<PolicyRule>, BoxError> {
+    let raw: HashMap<String, String> = cmd("HGETALL").arg(key).query_async(conn).await?;
+    let mut rules = Vec::with_capacity(raw.len());
+    for (id, json) in raw {
+        match serde_json::from_str::<PolicyRule>(&json) {
+            Ok(rule) => rules.push(rule),
+            Err(e) => log::warn!("ignoring unreadable policy {:?} in {}: {}", id, key, e),
+        }
+    }
+    Ok(rules)
+}
+
+pub(crate) async fn save_policy<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    rule: &PolicyRule,
+) -> Result<(), BoxError> {
+    let json = serde_json::to_string(rule)?;
+    let _: () = cmd("HSET").arg(key).arg(&rule.id).arg(json).query_async(conn).await?;
+    Ok(())
+}
+
+pub(crate) async fn delete_policy<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    id: &str,
+) -> Result<bool, BoxError> {
+    let removed: u64 = cmd("HDEL").arg(key).arg(id).query_async(conn).await?;
+    Ok(removed > 0)
 }
@@ -0,0 +1,139 @@
+use crate::adapters::compression::PayloadCompression;
+use crate::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use crate::adapters::redis_adapter::DeadpoolRedisAdapter;
+use crate::adapters::redis_cluster_adapter::ClusterRedisAdapter;
+use crate::adapters::redis_sentinel_adapter::SentinelRedisAdapter;
+use crate::config::{Config, RedisMode};
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
+use crate::ports::{BoxError, PolicyStore, RedisPort};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The Redis adapter picked by `redis_mode`. The ports are generic rather
+/// than object safe, so the choice is made here and every call forwarded.
+#[derive(Clone)]
+pub enum RedisBackend {
+    Single(DeadpoolRedisAdapter),
+    Cluster(ClusterRedisAdapter),
+    Sentinel(SentinelRedisAdapter),
+    Memory(MemoryRedisAdapter),
+}
+
+impl RedisBackend {
+    pub fn from_config(config: &Config) -> Result<Self, BoxError> {
+        let compression = PayloadCompression::from_config(config);
+        let runtime = Some(deadpool_redis::Runtime::Tokio1);
+        Ok(match config.redis_mode {
+            RedisMode::Single => {
+                let pool = deadpool_redis::Config::from_url(config.redis_url.clone()).create_pool(runtime)?;
+                Self::Single(DeadpoolRedisAdapter { pool, compression })
+            }
+            RedisMode::Cluster => {
+                let pool = deadpool_redis::cluster::Config::from_urls(config.cluster_urls()).create_pool(runtime)?;
+                Self::Cluster(ClusterRedisAdapter { pool, compression })
+            }
+            RedisMode::Sentinel => Self::Sentinel(SentinelRedisAdapter::from_config(config, compression)?),
+            RedisMode::Memory => Self::Memory(MemoryRedisAdapter::new(config.memory_max_entries)),
+        })
+    }
+}
+
+macro_rules! forward {
+    ($self:ident, $adapter:ident => $call:expr) => {
+        match $self {
+            RedisBackend::Single($adapter) => $call.await,
+            RedisBackend::Cluster($adapter) => $call.await,
+            RedisBackend::Sentinel($adapter) => $call.await,
+            RedisBackend::Memory($adapter) => $call.await,
+        }
+    };
+}
+
+impl RedisPort for RedisBackend {
+    async fn multi_hgetall(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
+        forward!(self, a => a.multi_hgetall(keys))
+    }
+
+    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
+        forward!(self, a => a.hgetall(key))
+    }
+
+    async fn write_cache_and_clear(
+        &self,
+        key: &str,
+        page: &StoredPage,
+        fetched_at: u64,
+        cache_ttl: u64,
+    ) -> Result<(), BoxError> {
+        forward!(self, a => a.write_cache_and_clear(key, page, fetched_at, cache_ttl))
+    }
+
+    async fn set_inflight_fields(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+        inflight_ttl: u64,
+    ) -> Result<(), BoxError> {
+        forward!(self, a => a.set_inflight_fields(key, last_mongo, last_crawler, inflight_ttl))
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        forward!(self, a => a.mark_refresh(key, last_mongo, last_crawler))
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        forward!(self, a => a.get_failures(keys))
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        forward!(self, a => a.set_failure(key, failure, ttl))
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        forward!(self, a => a.clear_failure(key))
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        forward!(self, a => a.remaining_ttl(key))
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        forward!(self, a => a.scan_prefix(prefix))
+    }
+
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        forward!(self, a => a.scan_match(prefix, pattern))
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        forward!(self, a => a.delete_keys(keys))
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        forward!(self, a => a.ping())
+    }
+}
+
+impl PolicyStore for RedisBackend {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        forward!(self, a => a.load_policies(key))
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        forward!(self, a => a.save_policy(key, rule))
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        forward!(self, a => a.delete_policy(key, id))
+    }
+}
@@ -0,0 +1,213 @@
+use crate::adapters::compression::PayloadCompression;
+use crate::adapters::redis_adapter as commands;
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
+use crate::ports::{BoxError, PolicyStore, RedisPort};
+use deadpool_redis::cluster::Pool;
+use deadpool_redis::redis::cluster_routing::{RoutingInfo, SingleNodeRoutingInfo, get_slot};
+use deadpool_redis::redis::{Value, cmd, from_redis_value};
+use futures::future::try_join_all;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// `RedisPort` for Redis Cluster. The cluster connection follows MOVED and
+/// ASK redirections itself; multi-key reads are split so that every
+/// pipeline only touches keys of a single slot.
+#[derive(Clone)]
+pub struct ClusterRedisAdapter {
+    pub pool: Pool,
+    pub compression: PayloadCompression,
+}
+
+/// Positions of `keys` grouped by cluster slot, groups in first-seen order.
+pub fn group_by_slot(keys: &[String]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut by_slot: HashMap<u16, usize> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        let slot = get_slot(key.as_bytes());
+        let group = *by_slot.entry(slot).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    groups
+}
+
+impl ClusterRedisAdapter {
+    /// Runs `query` once per slot, concurrently, and puts the results back in
+    /// the order of `keys`.
+    async fn per_slot<T, F, Fut>(&self, keys: &[String], query: F) -> Result<Vec<T>, BoxError>
+    where
+        T: Default + Clone,
+        F: Fn(deadpool_redis::cluster::ClusterConnection, Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<T>, BoxError>>,
+    {
+        let conn = self.pool.get().await?;
+        let groups = group_by_slot(keys);
+        let results = try_join_all(groups.iter().map(|group| {
+            let slot_keys = group.iter().map(|&i| keys[i].clone()).collect();
+            query((*conn).clone(), slot_keys)
+        }))
+        .await?;
+        let mut ordered = vec![T::default(); keys.len()];
+        for (group, values) in groups.iter().zip(results) {
+            for (&i, value) in group.iter().zip(values) {
+                ordered[i] = value;
+            }
+        }
+        Ok(ordered)
+    }
+}
+
+/// Address of the master serving each slot range in a `CLUSTER SLOTS` reply,
+/// each master once.
+fn master_addresses(slots: &Value) -> Result<Vec<(String, u16)>, BoxError> {
+    let mut masters: Vec<(String, u16)> = Vec::new();
+    for range in from_redis_value::<Vec<Vec<Value>>>(slots)? {
+        // [first slot, last slot, [host, port, id, ...], replicas...]
+        let Some(master) = range.get(2) else { continue };
+        let node: Vec<Value> = from_redis_value(master)?;
+        let (Some(host), Some(port)) = (node.first(), node.get(1)) else { continue };
+        let address = (from_redis_value::<String>(host)?, from_redis_value::<u16>(port)?);
+        if !masters.contains(&address) {
+            masters.push(address);
+        }
+    }
+    Ok(masters)
+}
+
+impl RedisPort for ClusterRedisAdapter {
+    async fn multi_hgetall(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
+        self.per_slot(keys, |mut conn, slot_keys| async move {
+            commands::multi_hgetall(&mut conn, &slot_keys).await
+        })
+        .await
+    }
+
+    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::hgetall(&mut conn, key).await
+    }
+
+    async fn write_cache_and_clear(
+        &self,
+        key: &str,
+        page: &StoredPage,
+        fetched_at: u64,
+        cache_ttl: u64,
+    ) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::write_cache_and_clear(&mut conn, &self.compression, key, page, fetched_at, cache_ttl).await
+    }
+
+    async fn set_inflight_fields(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+        inflight_ttl: u64,
+    ) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::set_inflight_fields(&mut conn, key, last_mongo, last_crawler, inflight_ttl).await
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::mark_refresh(&mut conn, key, last_mongo, last_crawler).await
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        self.per_slot(keys, |mut conn, slot_keys| async move {
+            commands::get_failures(&mut conn, &slot_keys).await
+        })
+        .await
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::set_failure(&mut conn, key, failure, ttl).await
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::delete(&mut conn, key).await
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::remaining_ttl(&mut conn, key).await
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        self.scan_match(prefix, "*").await
+    }
+
+    /// Scans every master in turn; a resharding during the scan can make it
+    /// miss keys that moved between nodes.
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        let slots = conn
+            .route_command(cmd("CLUSTER").arg("SLOTS"), RoutingInfo::SingleNode(SingleNodeRoutingInfo::Random))
+            .await?;
+        let pattern = commands::match_pattern(prefix, pattern);
+        let mut keys = Vec::new();
+        for (host, port) in master_addresses(&slots)? {
+            let node = RoutingInfo::SingleNode(SingleNodeRoutingInfo::ByAddress { host, port });
+            let mut cursor: u64 = 0;
+            loop {
+                let scan = cmd("SCAN").arg(cursor).arg("MATCH").arg(&pattern).arg("COUNT").arg(commands::SCAN_COUNT).to_owned();
+                let (next, batch): (u64, Vec<String>) = from_redis_value(&conn.route_command(&scan, node.clone()).await?)?;
+                keys.extend(batch);
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        let conn = self.pool.get().await?;
+        let deleted = try_join_all(group_by_slot(keys).into_iter().map(|group| {
+            let mut conn = (*conn).clone();
+            let slot_keys: Vec<String> = group.iter().map(|&i| keys[i].clone()).collect();
+            async move { commands::delete_keys(&mut conn, &slot_keys).await }
+        }))
+        .await?;
+        Ok(deleted.into_iter().sum())
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::ping(&mut conn).await
+    }
+}
+
+impl PolicyStore for ClusterRedisAdapter {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::load_policies(&mut conn, key).await
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::save_policy(&mut conn, key, rule).await
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        let mut conn = self.pool.get().await?;
+        commands::delete_policy(&mut conn, key, id).await
+    }
+}
@@ -0,0 +1,180 @@
+use crate::adapters::compression::PayloadCompression;
+use crate::adapters::redis_adapter as commands;
+use crate::config::Config;
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
+use crate::ports::{BoxError, PolicyStore, RedisPort};
+use deadpool_redis::redis::{ConnectionAddr, ErrorKind, IntoConnectionInfo, RedisError};
+use deadpool_redis::sentinel::{Config as SentinelConfig, Pool, SentinelNodeConnectionInfo, SentinelServerType, TlsMode};
+use deadpool_redis::{RedisConnectionInfo, Runtime};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Redis behind Sentinel. Every new connection asks the sentinels for the
+/// current master, so after a failover the pool only has to drop the
+/// connections that still point at the old one.
+#[derive(Clone)]
+pub struct SentinelRedisAdapter {
+    pub master: Pool,
+    /// When set, `multi_hgetall` is served by a replica.
+    pub replicas: Option<Pool>,
+    pub compression: PayloadCompression,
+}
+
+impl SentinelRedisAdapter {
+    pub fn from_config(config: &Config, compression: PayloadCompression) -> Result<Self, BoxError> {
+        let node = node_connection_info(&config.redis_url)?;
+        let pool = |server_type| {
+            SentinelConfig::from_urls(config.redis_sentinel_urls.clone(), config.redis_sentinel_master.clone(), server_type)
+                .with_node_connection_info(Some(node.clone()))
+                .create_pool(Some(Runtime::Tokio1))
+        };
+        let master = pool(SentinelServerType::Master)?;
+        let replicas = match config.redis_sentinel_replica_reads {
+            true => Some(pool(SentinelServerType::Replica)?),
+            false => None,
+        };
+        Ok(Self { master, replicas, compression })
+    }
+}
+
+/// Whether `e` means the connection no longer reaches a usable master: the
+/// node went away, or it was demoted and now rejects writes.
+pub fn is_failover_error(e: &BoxError) -> bool {
+    e.downcast_ref::<RedisError>().is_some_and(|e| {
+        e.kind() == ErrorKind::ReadOnly || e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal()
+    })
+}
+
+/// Credentials, database and TLS for the data nodes, taken from `redis_url`.
+fn node_connection_info(redis_url: &str) -> Result<SentinelNodeConnectionInfo, BoxError> {
+    let info = redis_url.into_connection_info()?;
+    let tls_mode = match info.addr {
+        ConnectionAddr::TcpTls { insecure: true, .. } => Some(TlsMode::Insecure),
+        ConnectionAddr::TcpTls { .. } => Some(TlsMode::Secure),
+        _ => None,
+    };
+    Ok(SentinelNodeConnectionInfo {
+        tls_mode,
+        redis_connection_info: Some(RedisConnectionInfo::from(info.redis)),
+    })
+}
+
+// Runs `$call` on a master connection. On a failover error the connection
+// and every idle one are dropped, so the retry resolves the master afresh.
+macro_rules! on_master {
+    ($self:ident, $conn:ident => $call:expr) => {{
+        let mut $conn = $self.master.get().await?;
+        match $call.await {
+            Err(e) if is_failover_error(&e) => {
+                log::warn!("redis master unavailable ({}), reconnecting through sentinel", e);
+                drop(deadpool_redis::sentinel::Connection::take($conn));
+                $self.master.retain(|_, _| false);
+                let mut $conn = $self.master.get().await?;
+                $call.await
+            }
+            result => result,
+        }
+    }};
+}
+
+impl RedisPort for SentinelRedisAdapter {
+    async fn multi_hgetall(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
+        if let Some(replicas) = &self.replicas {
+            let read = async {
+                let mut conn = replicas.get().await?;
+                commands::multi_hgetall(&mut conn, keys).await
+            };
+            match read.await {
+                Ok(hashes) => return Ok(hashes),
+                Err(e) => log::warn!("replica read failed, using the master: {}", e),
+            }
+        }
+        on_master!(self, conn => commands::multi_hgetall(&mut conn, keys))
+    }
+
+    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
+        on_master!(self, conn => commands::hgetall(&mut conn, key))
+    }
+
+    async fn write_cache_and_clear(
+        &self,
+        key: &str,
+        page: &StoredPage,
+        fetched_at: u64,
+        cache_ttl: u64,
+    ) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::write_cache_and_clear(&mut conn, &self.compression, key, page, fetched_at, cache_ttl))
+    }
+
+    async fn set_inflight_fields(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+        inflight_ttl: u64,
+    ) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::set_inflight_fields(&mut conn, key, last_mongo, last_crawler, inflight_ttl))
+    }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::mark_refresh(&mut conn, key, last_mongo, last_crawler))
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        on_master!(self, conn => commands::get_failures(&mut conn, keys))
+    }
+
+    async fn set_failure(&self, key: &str, failure: &UrlFailure, ttl: u64) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::set_failure(&mut conn, key, failure, ttl))
+    }
+
+    async fn clear_failure(&self, key: &str) -> Result<bool, BoxError> {
+        on_master!(self, conn => commands::delete(&mut conn, key))
+    }
+
+    async fn remaining_ttl(&self, key: &str) -> Result<Option<Duration>, BoxError> {
+        on_master!(self, conn => commands::remaining_ttl(&mut conn, key))
+    }
+
+    async fn scan_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        on_master!(self, conn => commands::scan_prefix(&mut conn, prefix))
+    }
+
+    async fn scan_match(&self, prefix: &str, pattern: &str) -> Result<Vec<String>, BoxError> {
+        on_master!(self, conn => commands::scan_match(&mut conn, prefix, pattern))
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64, BoxError> {
+        on_master!(self, conn => commands::delete_keys(&mut conn, keys))
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::ping(&mut conn))
+    }
+}
+
+impl PolicyStore for SentinelRedisAdapter {
+    async fn load_policies(&self, key: &str) -> Result<Vec<PolicyRule>, BoxError> {
+        on_master!(self, conn => commands::load_policies(&mut conn, key))
+    }
+
+    async fn save_policy(&self, key: &str, rule: &PolicyRule) -> Result<(), BoxError> {
+        on_master!(self, conn => commands::save_policy(&mut conn, key, rule))
+    }
+
+    async fn delete_policy(&self, key: &str, id: &str) -> Result<bool, BoxError> {
+        on_master!(self, conn => commands::delete_policy(&mut conn, key, id))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::domain::StoredPage;
+use crate::ports::{BoxError, MongoPort};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Columns of the `pages` table besides `url`. They mirror the cache hash
+/// fields, so rows convert with `StoredPage::{to,from}_cache_fields`.
+const PAGE_COLUMNS: [&str; 7] = ["data", "data_format", "status_code", "content_type", "final_url", "crawled_at", "etag"];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS pages (
+        url          TEXT PRIMARY KEY NOT NULL,
+        data         TEXT NOT NULL,
+        data_format  TEXT,
+        status_code  INTEGER,
+        content_type TEXT,
+        final_url    TEXT,
+        crawled_at   INTEGER,
+        etag         TEXT
+    );
+";
+
+/// URLs per `IN (...)` query, well below SQLite's bound parameter limit.
+const QUERY_CHUNK: usize = 500;
+
+/// Page store in an embedded SQLite database, for deployments without a
+/// MongoDB server. One row per URL; saving a page replaces the row.
+#[derive(Clone)]
+pub struct SqliteAdapter {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteAdapter {
+    /// Opens or creates the database at `path` and its schema.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BoxError> {
+        let conn = Connection::open(path)?;
+        // WAL lets an operator read the file while the service writes it.
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        Self::with_connection(conn)
+    }
+
+    /// A private database that lives as long as the adapter and its clones.
+    pub fn in_memory() -> Result<Self, BoxError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, BoxError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    /// Runs `f` on the connection off the async runtime.
+    async fn run<T, F>(&self, f: F) -> Result<T, BoxError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, BoxError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+impl MongoPort for SqliteAdapter {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        let mut seen = HashSet::new();
+        let distinct: Vec<String> = urls.iter().filter(|u| seen.insert(*u)).cloned().collect();
+        self.run(move |conn| {
+            let mut pages = HashMap::new();
+            for chunk in distinct.chunks(QUERY_CHUNK) {
+                let sql = format!(
+                    "SELECT url, {} FROM pages WHERE url IN ({})",
+                    PAGE_COLUMNS.join(", "),
+                    vec!["?"; chunk.len()].join(", ")
+                );
+                let mut stmt = conn.prepare_cached(&sql)?;
+                let mut rows = stmt.query(params_from_iter(chunk))?;
+                while let Some(row) = rows.next()? {
+                    let url: String = row.get(0)?;
+                    let mut fields = HashMap::new();
+                    for (i, column) in PAGE_COLUMNS.iter().enumerate() {
+                        // integers come back as text like every cache field
+                        let value: Option<rusqlite::types::Value> = row.get(i + 1)?;
+                        let text = match value {
+                            Some(rusqlite::types::Value::Text(s)) => s,
+                            Some(rusqlite::types::Value::Integer(n)) => n.to_string(),
+                            _ => continue,
+                        };
+                        fields.insert(column.to_string(), text);
+                    }
+                    match StoredPage::from_cache_fields(&fields) {
+                        Some(page) => {
+                            pages.insert(url, page);
+                        }
+                        None => log::warn!("skipping unreadable page row for {}", url),
+                    }
+                }
+            }
+            Ok(pages)
+        })
+        .await
+    }
+
+    async fn save_pages(&self, pages: &HashMap<String, StoredPage>) -> Result<(), BoxError> {
+        let rows: Vec<(String, HashMap<&'static str, String>)> =
+            pages.iter().map(|(url, page)| (url.clone(), page.to_cache_fields().into_iter().collect())).collect();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let sql = format!(
+                    "INSERT OR REPLACE INTO pages (url, {}) VALUES (?{})",
+                    PAGE_COLUMNS.join(", "),
+                    ", ?".repeat(PAGE_COLUMNS.len())
+                );
+                let mut stmt = tx.prepare_cached(&sql)?;
+                for (url, fields) in &rows {
+                    let int = |column: &str| fields.get(column).and_then(|v| v.parse::<i64>().ok());
+                    stmt.execute(params![
+                        url,
+                        fields.get("data"),
+                        fields.get("data_format"),
+                        int("status_code"),
+                        fields.get("content_type"),
+                        fields.get("final_url"),
+                        int("crawled_at"),
+                        fields.get("etag"),
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        self.run(|conn| {
+            conn.query_row("SELECT 1", [], |_| Ok(())).optional()?;
+            Ok(())
+        })
+        .await
+    }
+}
@@ -0,0 +1,62 @@
+use crate::adapters::mongo_adapter::{MongoAdapter, QueryOptions};
+use crate::adapters::sqlite_adapter::SqliteAdapter;
+use crate::config::{Config, StorageMode};
+use crate::domain::StoredPage;
+use crate::ports::{BoxError, MongoPort};
+use std::collections::HashMap;
+
+/// The page store picked by `storage`, forwarding every call like
+/// `RedisBackend` does for the Redis modes.
+#[derive(Clone)]
+pub enum StorageBackend {
+    Mongo(MongoAdapter),
+    Sqlite(SqliteAdapter),
+}
+
+impl StorageBackend {
+    /// Connects the configured store. For Mongo this also checks the indexes
+    /// when `mongo_manage_indexes` is set; a server that is still starting
+    /// is only logged, and readiness reports it until it is up.
+    pub async fn from_config(config: &Config) -> Result<Self, BoxError> {
+        Ok(match config.storage {
+            StorageMode::Mongo => {
+                let options = mongodb::options::ClientOptions::parse(&config.mongo_url).await?;
+                let client = mongodb::Client::with_options(options)?;
+                let coll = client
+                    .database(&config.mongo_database)
+                    .collection::<mongodb::bson::Document>(&config.mongo_collection);
+                let adapter = MongoAdapter { coll, query: QueryOptions::from_config(config) };
+                if config.mongo_manage_indexes
+                    && let Err(e) = adapter.ensure_indexes(&config.mongo_indexes).await
+                {
+                    log::warn!("could not check MongoDB indexes: {}", e);
+                }
+                Self::Mongo(adapter)
+            }
+            StorageMode::Sqlite => Self::Sqlite(SqliteAdapter::open(&config.sqlite_path)?),
+        })
+    }
+}
+
+macro_rules! forward {
+    ($self:ident, $adapter:ident => $call:expr) => {
+        match $self {
+            StorageBackend::Mongo($adapter) => $call.await,
+            StorageBackend::Sqlite($adapter) => $call.await,
+        }
+    };
+}
+
+impl MongoPort for StorageBackend {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        forward!(self, a => a.find_by_urls(urls))
+    }
+
+    async fn save_pages(&self, pages: &HashMap<String, StoredPage>) -> Result<(), BoxError> {
+        forward!(self, a => a.save_pages(pages))
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        forward!(self, a => a.ping())
+    }
+}
@@ -0,0 +1,398 @@
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, Responder, delete, dev::Payload, error, get, http::header, post, put,
+    web,
+};
+use groove_throttle::adapters::chaos::{ChaosControl, FaultPlan, RecordedCall};
+use groove_throttle::config::{Config, ConfigHandle};
+use groove_throttle::domain::{FailureReport, PurgeScope};
+use groove_throttle::policy::PolicyRule;
+use groove_throttle::ports::PolicyStore;
+use serde::{Deserialize, Serialize};
+use std::future::{Ready, ready};
+use std::sync::Arc;
+
+use crate::{ConcreteService, record_failures};
+
+/// Extractor that only succeeds when the request carries `admin_token` or
+/// one of `admin_tokens` as a bearer token. Without any token configured the
+/// admin API is disabled.
+pub struct AdminAuth {
+    /// Name of the matching token, `admin` for `admin_token`.
+    pub caller: String,
+    /// Address of the connection's other end, a proxy if there is one.
+    pub peer: String,
+    /// `Forwarded` or `X-Forwarded-For` as sent. Any client can set these,
+    /// so the value is only logged, never trusted.
+    pub forwarded_for: Option<String>,
+}
+
+impl AdminAuth {
+    /// Records an admin action under the `audit` log target.
+    fn audit(&self, action: std::fmt::Arguments<'_>) {
+        match &self.forwarded_for {
+            Some(forwarded) => log::info!(
+                target: "audit",
+                "{} ({}, unverified forwarded-for {:?}): {}",
+                self.caller,
+                self.peer,
+                forwarded,
+                action
+            ),
+            None => log::info!(target: "audit", "{} ({}): {}", self.caller, self.peer, action),
+        }
+    }
+}
+
+impl FromRequest for AdminAuth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authorize(req))
+    }
+}
+
+/// Extractor for the crawler's failure reports: succeeds when the request
+/// carries `failure_ingest_token` or any admin token as a bearer token.
+pub struct IngestAuth {
+    /// `crawler` for `failure_ingest_token`, otherwise the admin token's name.
+    pub caller: String,
+}
+
+impl FromRequest for IngestAuth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authorize_ingest(req))
+    }
+}
+
+fn loaded_config(req: &HttpRequest) -> Result<Arc<Config>, actix_web::Error> {
+    let handle = req
+        .app_data::<web::Data<ConfigHandle>>()
+        .ok_or_else(|| error::ErrorInternalServerError("config handle not registered"))?;
+    Ok(handle.load())
+}
+
+/// `admin_token` and `admin_tokens` as (caller, token) pairs.
+fn admin_tokens(config: &Config) -> Vec<(&str, &str)> {
+    config
+        .admin_token
+        .as_deref()
+        .map(|token| ("admin", token))
+        .into_iter()
+        .chain(config.admin_tokens.iter().map(|(name, token)| (name.as_str(), token.as_str())))
+        .collect()
+}
+
+/// The caller whose token the request presents as its bearer token.
+fn bearer_caller<'a>(req: &HttpRequest, tokens: &[(&'a str, &str)]) -> Option<&'a str> {
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))?;
+    // Every token is compared so the time taken does not reveal which matched.
+    tokens.iter().fold(None, |found, (name, token)| {
+        if constant_time_eq(presented.as_bytes(), token.as_bytes()) { Some(*name) } else { found }
+    })
+}
+
+fn authorize(req: &HttpRequest) -> Result<AdminAuth, actix_web::Error> {
+    let config = loaded_config(req)?;
+    let tokens = admin_tokens(&config);
+    if tokens.is_empty() {
+        return Err(error::ErrorNotFound("admin API is disabled"));
+    }
+    match bearer_caller(req, &tokens) {
+        Some(caller) => Ok(AdminAuth {
+            caller: caller.to_string(),
+            peer: req.peer_addr().map_or_else(|| "unknown".to_string(), |addr| addr.to_string()),
+            forwarded_for: [header::FORWARDED, header::X_FORWARDED_FOR]
+                .iter()
+                .find_map(|name| req.headers().get(name))
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned()),
+        }),
+        None => Err(error::ErrorUnauthorized("missing or invalid admin token")),
+    }
+}
+
+fn authorize_ingest(req: &HttpRequest) -> Result<IngestAuth, actix_web::Error> {
+    let config = loaded_config(req)?;
+    let mut tokens = admin_tokens(&config);
+    tokens.extend(config.failure_ingest_token.as_deref().map(|token| ("crawler", token)));
+    if tokens.is_empty() {
+        return Err(error::ErrorNotFound("failure ingest is disabled"));
+    }
+    match bearer_caller(req, &tokens) {
+        Some(caller) => Ok(IngestAuth { caller: caller.to_string() }),
+        None => Err(error::ErrorUnauthorized("missing or invalid failure ingest token")),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Re-reads the config file and environment and applies the reloadable fields.
+#[post("/admin/reload")]
+async fn reload(_auth: AdminAuth, handle: web::Data<ConfigHandle>) -> impl Responder {
+    match handle.reload() {
+        Ok(outcome) => HttpResponse::Ok().json(outcome),
+        Err(e) => {
+            log::error!("config reload failed: {}", e);
+            HttpResponse::BadRequest().body(e.to_string())
+        }
+    }
+}
+
+/// Rules currently in effect on this instance, in evaluation order.
+#[get("/admin/policies")]
+async fn list_policies(_auth: AdminAuth, svc: web::Data<Arc<ConcreteService>>) -> impl Responder {
+    HttpResponse::Ok().json(svc.policies.rules())
+}
+
+/// Creates or replaces the rule with the id from the path.
+#[put("/admin/policies/{id}")]
+async fn put_policy(
+    _auth: AdminAuth,
+    id: web::Path<String>,
+    rule: web::Json<PolicyRule>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let mut rule = rule.into_inner();
+    rule.id = id.into_inner();
+    let config = svc.config.load();
+    if let Err(e) = rule.validate_with(&config) {
+        return HttpResponse::BadRequest().body(e);
+    }
+    let key = config.policy_key.clone();
+    if let Err(e) = svc.redis.save_policy(&key, &rule).await {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    refresh_after_edit(&svc, &key).await;
+    HttpResponse::Ok().json(rule)
+}
+
+#[delete("/admin/policies/{id}")]
+async fn delete_policy(
+    _auth: AdminAuth,
+    id: web::Path<String>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let key = svc.config.load().policy_key.clone();
+    match svc.redis.delete_policy(&key, &id).await {
+        Ok(true) => {
+            refresh_after_edit(&svc, &key).await;
+            HttpResponse::NoContent().finish()
+        }
+        Ok(false) => HttpResponse::NotFound().body(format!("no policy with id {:?}", id.as_str())),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+struct UrlQuery {
+    url: String,
+}
+
+/// Shows which rule, if any, applies to a URL and the resulting values.
+#[get("/admin/policies/resolve")]
+async fn resolve_policy(
+    _auth: AdminAuth,
+    query: web::Query<UrlQuery>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let config = svc.config.load();
+    HttpResponse::Ok().json(svc.policies.resolve(&query.url, &config))
+}
+
+async fn refresh_after_edit(svc: &ConcreteService, key: &str) {
+    // Other instances pick the edit up on their next periodic refresh.
+    if let Err(e) = svc.policies.refresh_from(&svc.redis, key).await {
+        log::warn!("policy refresh after edit failed: {}", e);
+    }
+}
+
+/// Marks URLs as failed, same as the crawler's `/api/failures` ingest path.
+#[post("/admin/failures")]
+async fn put_failures(
+    _auth: AdminAuth,
+    reports: web::Json<Vec<FailureReport>>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    record_failures(&svc, reports.into_inner()).await
+}
+
+/// Forgets the recorded failure so the URL is crawled on its next request.
+#[delete("/admin/failures")]
+async fn delete_failure(
+    _auth: AdminAuth,
+    query: web::Query<UrlQuery>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    match svc.clear_failure(&query.url).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("no failure recorded for {:?}", query.url)),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Cache entry, throttle windows, storage and the decision `process` would
+/// make for a URL, read without changing anything.
+#[get("/admin/inspect")]
+async fn inspect(
+    _auth: AdminAuth,
+    query: web::Query<UrlQuery>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    match svc.inspect(&query.url).await {
+        Ok(inspection) => HttpResponse::Ok().json(inspection),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Serialize)]
+struct PurgeOutcome {
+    purged: usize,
+    urls: Vec<String>,
+}
+
+/// Drops cache entries by exact URL, host, URL prefix or URL glob, given as
+/// `{"url": ...}`, `{"host": ...}`, `{"prefix": ...}` or `{"pattern": ...}`.
+/// Storage still has the page, so pair this with a recrawl when the page
+/// itself changed.
+#[post("/admin/purge")]
+async fn purge(
+    auth: AdminAuth,
+    scope: web::Json<PurgeScope>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let scope = scope.into_inner();
+    let empty = match &scope {
+        PurgeScope::Url(s) | PurgeScope::Host(s) | PurgeScope::Prefix(s) | PurgeScope::Pattern(s) => {
+            s.trim().is_empty()
+        }
+    };
+    if empty {
+        return HttpResponse::BadRequest().body("purge scope must not be empty");
+    }
+    match svc.purge(&scope).await {
+        Ok(urls) => {
+            auth.audit(format_args!("purged {} cache entries for {:?}", urls.len(), scope));
+            HttpResponse::Ok().json(PurgeOutcome { purged: urls.len(), urls })
+        }
+        Err(e) => {
+            auth.audit(format_args!("purge for {:?} failed: {}", scope, e));
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RecrawlOutcome {
+    dispatched: Vec<String>,
+}
+
+/// Sends the URLs in the body to the crawler right away, ignoring
+/// `crawler_prevent_ms` and recorded failures.
+#[post("/admin/recrawl")]
+async fn recrawl(
+    auth: AdminAuth,
+    urls: web::Json<Vec<String>>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    match svc.force_crawl(&urls).await {
+        Ok(dispatched) => {
+            auth.audit(format_args!("forced a crawl of {:?}", dispatched));
+            HttpResponse::Ok().json(RecrawlOutcome { dispatched })
+        }
+        Err(e) => {
+            auth.audit(format_args!("forced crawl of {} urls failed: {}", urls.len(), e));
+            HttpResponse::InternalServerError().body(e.to_string())
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChaosStatus {
+    port: &'static str,
+    plan: FaultPlan,
+    calls: Vec<RecordedCall>,
+}
+
+/// The chaos controls of the wrapped ports, or 404 unless `chaos_enabled`.
+fn chaos_controls(svc: &ConcreteService) -> Result<[&ChaosControl; 3], HttpResponse> {
+    if !svc.config.load().chaos_enabled {
+        return Err(HttpResponse::NotFound().body("chaos testing is disabled"));
+    }
+    Ok([&svc.redis.control, &svc.mongo.control, &svc.crawler.control])
+}
+
+/// Current fault plans and recorded calls per port.
+#[get("/admin/chaos")]
+async fn get_chaos(_auth: AdminAuth, svc: web::Data<Arc<ConcreteService>>) -> impl Responder {
+    match chaos_controls(&svc) {
+        Ok(controls) => HttpResponse::Ok().json(
+            controls
+                .iter()
+                .map(|c| ChaosStatus { port: c.port(), plan: c.plan(), calls: c.calls() })
+                .collect::<Vec<_>>(),
+        ),
+        Err(response) => response,
+    }
+}
+
+/// Replaces the fault plan of one port (`redis`, `storage` or `crawler`).
+#[put("/admin/chaos/{port}")]
+async fn put_chaos(
+    _auth: AdminAuth,
+    port: web::Path<String>,
+    plan: web::Json<FaultPlan>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let controls = match chaos_controls(&svc) {
+        Ok(controls) => controls,
+        Err(response) => return response,
+    };
+    let Some(control) = controls.iter().find(|c| c.port() == port.as_str()) else {
+        return HttpResponse::NotFound().body(format!("no port named {:?}", port.as_str()));
+    };
+    match control.set_plan(plan.into_inner()) {
+        Ok(()) => {
+            log::warn!("chaos plan for {} changed: {:?}", control.port(), control.plan());
+            HttpResponse::Ok().json(control.plan())
+        }
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+/// Stops injecting faults and recording on every port.
+#[delete("/admin/chaos")]
+async fn delete_chaos(_auth: AdminAuth, svc: web::Data<Arc<ConcreteService>>) -> impl Responder {
+    match chaos_controls(&svc) {
+        Ok(controls) => {
+            controls.iter().for_each(|c| c.reset());
+            log::warn!("chaos faults switched off");
+            HttpResponse::NoContent().finish()
+        }
+        Err(response) => response,
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(reload)
+        .service(list_policies)
+        .service(resolve_policy)
+        .service(put_policy)
+        .service(delete_policy)
+        .service(put_failures)
+        .service(delete_failure)
+        .service(inspect)
+        .service(purge)
+        .service(recrawl)
+        .service(get_chaos)
+        .service(put_chaos)
+        .service(delete_chaos);
+}
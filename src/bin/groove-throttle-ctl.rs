@@ -0,0 +1,351 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use env_logger::Env;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use groove_throttle::adapters::crawler_backend::CrawlerBackend;
+use groove_throttle::adapters::redis_backend::RedisBackend;
+use groove_throttle::adapters::storage_backend::StorageBackend;
+use groove_throttle::config::{Config, RedisMode};
+use groove_throttle::domain::PurgeScope;
+use groove_throttle::inspect::{Decision, UrlInspection, Window};
+use groove_throttle::ports::BoxError;
+use groove_throttle::service::LoadReducerService;
+
+#[derive(Parser)]
+#[command(version, about = "Operator tools for the cache in front of the crawler")]
+struct Cli {
+    /// TOML config file, the same one the server uses. Environment variables
+    /// override values from the file.
+    #[arg(long, short, env = "CONFIG_FILE", global = true)]
+    config: Option<PathBuf>,
+
+    /// How to print results.
+    #[arg(long, short, value_enum, default_value_t = Output::Table, global = true)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the cache entry, throttle windows, storage and next decision for URLs.
+    Inspect {
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Drop cache entries by exact URL, host, URL prefix or URL pattern.
+    Purge {
+        #[command(flatten)]
+        scope: ScopeArgs,
+        /// List the entries that would be dropped without dropping them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Cache what storage has for the URLs in a file, one per line. Blank
+    /// lines and lines starting with `#` are skipped.
+    Warm {
+        file: PathBuf,
+        /// URLs per storage lookup.
+        #[arg(long, default_value_t = 500)]
+        batch_size: usize,
+    },
+    /// Send URLs to the crawler now, ignoring the throttle and recorded failures.
+    Enqueue {
+        #[arg(required_unless_present = "file")]
+        urls: Vec<String>,
+        /// Also read URLs from this file, one per line.
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Show the Mongo and crawler windows and recorded failures for URLs.
+    Throttle {
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+struct ScopeArgs {
+    /// Exactly this URL.
+    #[arg(long)]
+    url: Option<String>,
+    /// Every http(s) URL on this host.
+    #[arg(long)]
+    host: Option<String>,
+    /// Every URL starting with this string.
+    #[arg(long)]
+    prefix: Option<String>,
+    /// Every URL matching this Redis glob, e.g. 'https://example.com/*/amp'.
+    #[arg(long)]
+    pattern: Option<String>,
+}
+
+impl ScopeArgs {
+    fn into_scope(self) -> PurgeScope {
+        match (self.url, self.host, self.prefix, self.pattern) {
+            (Some(url), _, _, _) => PurgeScope::Url(url),
+            (_, Some(host), _, _) => PurgeScope::Host(host),
+            (_, _, Some(prefix), _) => PurgeScope::Prefix(prefix),
+            (_, _, _, Some(pattern)) => PurgeScope::Pattern(pattern),
+            // clap requires exactly one of them
+            (None, None, None, None) => unreachable!("no purge scope given"),
+        }
+    }
+}
+
+type Service = LoadReducerService<RedisBackend, StorageBackend, CrawlerBackend>;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Audit records go to stderr next to warnings, like on the server.
+    env_logger::init_from_env(Env::default().default_filter_or("warn,audit=info"));
+
+    let cli = Cli::parse();
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("configuration error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    match run(cli.command, cli.output, config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The service over the same backends the server builds from `config`,
+/// with the shared policy rules loaded. Index management is left to the
+/// server: a CLI run never touches indexes, and never waits on Mongo
+/// unless its command reads pages.
+async fn connect(mut config: Config) -> Result<Service, BoxError> {
+    if config.redis_mode == RedisMode::Memory {
+        log::warn!("redis_mode is \"memory\": this process only sees its own, empty store");
+    }
+    config.mongo_manage_indexes = false;
+    let redis = RedisBackend::from_config(&config).map_err(|e| format!("failed to create Redis pool: {}", e))?;
+    let storage =
+        StorageBackend::from_config(&config).await.map_err(|e| format!("failed to open page storage: {}", e))?;
+    let crawler =
+        CrawlerBackend::from_config(&config).map_err(|e| format!("failed to set up crawler dispatch: {}", e))?;
+    let policy_key = config.policy_key.clone();
+    let service = LoadReducerService::new(redis, storage, crawler, config);
+    if let Err(e) = service.policies.refresh_from(&service.redis, &policy_key).await {
+        log::warn!("cannot load policy rules, using the global settings: {}", e);
+    }
+    Ok(service)
+}
+
+async fn run(command: Command, output: Output, config: Config) -> Result<(), BoxError> {
+    let service = connect(config).await?;
+    let operator = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+    match command {
+        Command::Inspect { urls } => {
+            let mut inspections = Vec::new();
+            for url in &urls {
+                inspections.push(service.inspect(url).await?);
+            }
+            print(output, &inspections, || inspection_tables(&inspections))
+        }
+        Command::Purge { scope, dry_run } => {
+            let scope = scope.into_scope();
+            let urls = if dry_run {
+                service.purge_targets(&scope).await?
+            } else {
+                let urls = service.purge(&scope).await?;
+                log::info!(target: "audit", "{} (cli): purged {} cache entries for {:?}", operator, urls.len(), scope);
+                urls
+            };
+            let value = json!({ "dry_run": dry_run, "purged": urls.len(), "urls": urls });
+            print(output, &value, || url_table(&urls))
+        }
+        Command::Warm { file, batch_size } => {
+            let urls = read_url_file(&file)?;
+            let mut cached = Vec::new();
+            for batch in urls.chunks(batch_size.max(1)) {
+                cached.extend(service.warm(batch).await?);
+                log::info!("warmed {} of {} urls", cached.len(), urls.len());
+            }
+            let warmed: HashSet<&str> = cached.iter().map(String::as_str).collect();
+            let missing: Vec<&String> = urls.iter().filter(|u| !warmed.contains(u.as_str())).collect();
+            let value = json!({ "cached": cached, "missing": missing });
+            print(output, &value, || {
+                let mut table = Table::new(["URL", "RESULT"]);
+                cached.iter().for_each(|u| table.row([u.clone(), "cached".to_string()]));
+                missing.iter().for_each(|u| table.row([u.to_string(), "not in storage".to_string()]));
+                vec![table]
+            })
+        }
+        Command::Enqueue { mut urls, file } => {
+            if let Some(file) = file {
+                urls.extend(read_url_file(&file)?);
+            }
+            let dispatched = service.force_crawl(&urls).await?;
+            log::info!(target: "audit", "{} (cli): forced a crawl of {:?}", operator, dispatched);
+            print(output, &json!({ "dispatched": dispatched }), || url_table(&dispatched))
+        }
+        Command::Throttle { urls } => {
+            let mut states = Vec::new();
+            for url in &urls {
+                states.push(service.throttle_state(url).await?);
+            }
+            print(output, &states, || {
+                let mut table = Table::new(["URL", "POLICY", "MONGO WINDOW", "CRAWLER WINDOW", "FAILURE"]);
+                for state in &states {
+                    table.row([
+                        state.url.clone(),
+                        state.policy.name().to_string(),
+                        describe_window(&state.last_mongo_fetch),
+                        describe_window(&state.last_crawler_send),
+                        state.failure.as_ref().map_or("-".to_string(), |f| format!("{:?}", f.kind).to_lowercase()),
+                    ]);
+                }
+                vec![table]
+            })
+        }
+    }
+}
+
+fn print<T: Serialize>(output: Output, value: &T, tables: impl FnOnce() -> Vec<Table>) -> Result<(), BoxError> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Output::Table => {
+            let tables = tables();
+            for (i, table) in tables.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                print!("{}", table.render());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// URLs from `path`, one per line, skipping blank lines and `#` comments.
+fn read_url_file(path: &Path) -> Result<Vec<String>, BoxError> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn url_table(urls: &[String]) -> Vec<Table> {
+    let mut table = Table::new(["URL"]);
+    urls.iter().for_each(|u| table.row([u.clone()]));
+    vec![table]
+}
+
+/// One two-column table per URL; the fields do not fit side by side.
+fn inspection_tables(inspections: &[UrlInspection]) -> Vec<Table> {
+    inspections
+        .iter()
+        .map(|i| {
+            let mut table = Table::new(["FIELD", "VALUE"]);
+            let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            table.row(["url".to_string(), i.url.clone()]);
+            table.row(["cache key".to_string(), i.cache_key.clone()]);
+            table.row(["policy".to_string(), i.policy.name().to_string()]);
+            table.row(["cached".to_string(), yes_no(i.cached)]);
+            table.row(["cache ttl".to_string(), or_dash(i.cache_ttl_ms.map(seconds))]);
+            table.row(["fetched at".to_string(), or_dash(i.fetched_at.map(timestamp))]);
+            table.row(["stale".to_string(), yes_no(i.stale)]);
+            table.row(["mongo window".to_string(), describe_window(&i.last_mongo_fetch)]);
+            table.row(["crawler window".to_string(), describe_window(&i.last_crawler_send)]);
+            table.row([
+                "failure".to_string(),
+                or_dash(i.failure.as_ref().map(|f| {
+                    let kind = format!("{:?}", f.kind).to_lowercase();
+                    match &f.reason {
+                        Some(reason) => format!("{} ({}) at {}", kind, reason, timestamp(f.failed_at)),
+                        None => format!("{} at {}", kind, timestamp(f.failed_at)),
+                    }
+                })),
+            ]);
+            table.row(["in storage".to_string(), yes_no(i.stored)]);
+            table.row(["decision".to_string(), describe_decision(i.decision)]);
+            table
+        })
+        .collect()
+}
+
+fn describe_decision(decision: Decision) -> String {
+    match decision {
+        Decision::ServeCached => "serve cached data".to_string(),
+        Decision::ServeStale { refresh: true } => "serve stale data and refresh it".to_string(),
+        Decision::ServeStale { refresh: false } => "serve stale data, refresh already under way".to_string(),
+        Decision::ServeFromStorage { recrawl: true } => "serve from storage and recrawl".to_string(),
+        Decision::ServeFromStorage { recrawl: false } => "serve from storage".to_string(),
+        Decision::ReportFailure => "report the recorded failure".to_string(),
+        Decision::SendToCrawler => "send to the crawler".to_string(),
+        Decision::WaitForCrawler => "wait for the crawler".to_string(),
+    }
+}
+
+fn describe_window(window: &Window) -> String {
+    match window.at {
+        None => "not set".to_string(),
+        Some(at) if window.open => format!("open, {} left (set {})", seconds(window.remaining_ms), timestamp(at)),
+        Some(at) => format!("passed (set {})", timestamp(at)),
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.1}s", ms as f64 / 1000.0)
+}
+
+fn timestamp(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map_or_else(|| ms.to_string(), |t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+/// Columns padded to their widest cell.
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new<const N: usize>(headers: [&str; N]) -> Self {
+        Self { rows: vec![headers.iter().map(|h| h.to_string()).collect()] }
+    }
+
+    fn row<const N: usize>(&mut self, cells: [String; N]) {
+        self.rows.push(cells.into());
+    }
+
+    fn render(&self) -> String {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|c| self.rows.iter().filter_map(|r| r.get(c)).map(|cell| cell.chars().count()).max().unwrap_or(0))
+            .collect();
+        let mut out = String::new();
+        for row in &self.rows {
+            let line: Vec<String> = row.iter().zip(&widths).map(|(cell, &w)| format!("{:<w$}", cell, w = w)).collect();
+            out.push_str(line.join("  ").trim_end());
+            out.push('\n');
+        }
+        out
+    }
+}
@@ -1,8 +1,214 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct UrlData {
+    pub url: String,
+    /// The stored payload as-is: a string for raw pages, or any JSON value
+    /// produced by the parsers. `null` for entries that only report a failure.
+    pub data: Value,
+    #[serde(flatten)]
+    pub meta: PageMeta,
+    /// Served past its soft TTL, or crawled longer ago than the configured
+    /// maximum age, while a refresh is under way.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// Recorded crawl failure; such URLs are not sent to the crawler until the
+    /// record expires. `data` is `null` unless an older copy is still stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<UrlFailure>,
+}
+
+impl UrlData {
+    /// The two-field shape older clients expect, where `data` is always a
+    /// string: structured payloads are passed as their JSON text. Entries
+    /// that only report a failure have no counterpart there and yield `None`.
+    pub fn into_legacy(self) -> Option<LegacyUrlData> {
+        let data = match self.data {
+            Value::Null if self.failure.is_some() => return None,
+            Value::String(s) => s,
+            other => other.to_string(),
+        };
+        Some(LegacyUrlData { url: self.url, data })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LegacyUrlData {
     pub url: String,
     pub data: String,
 }
 
+/// Which fields `/api` returns per URL.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseShape {
+    /// Data plus page metadata, staleness and failures.
+    Full,
+    /// Only `url` and `data`.
+    Legacy,
+}
+
+impl FromStr for ResponseShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "legacy" => Ok(Self::Legacy),
+            other => Err(format!("unknown response shape {:?}", other)),
+        }
+    }
+}
+
+/// How `/api` answers a request that lists the same URL more than once.
+/// Either way each distinct URL is looked up, marked and dispatched once.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateUrls {
+    /// One entry per occurrence, in request order.
+    Echo,
+    /// One entry per distinct URL, at its first occurrence.
+    Dedupe,
+}
+
+impl FromStr for DuplicateUrls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "echo" => Ok(Self::Echo),
+            "dedupe" => Ok(Self::Dedupe),
+            other => Err(format!("unknown duplicate URL handling {:?}", other)),
+        }
+    }
+}
+
+/// Which cache entries an admin purge drops.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PurgeScope {
+    /// Exactly this URL.
+    Url(String),
+    /// Every http(s) URL on this host, on any port.
+    Host(String),
+    /// Every URL starting with this string, compared as requested.
+    Prefix(String),
+    /// Every URL matching this Redis glob (`*`, `?`, `[...]`, `\` to escape).
+    Pattern(String),
+}
+
+/// What the crawler recorded about a fetch, alongside the page body.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PageMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Where the fetch ended up after redirects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+    /// When the crawler fetched the page, in ms since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crawled_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    /// e.g. 404 or 410; kept for `negative_ttl_permanent_sec`.
+    Permanent,
+    /// e.g. timeouts or 5xx; kept for `negative_ttl_temporary_sec`.
+    Temporary,
+}
+
+/// A crawl failure reported for `url`, by the crawler or an operator.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FailureReport {
+    pub url: String,
+    pub kind: FailureKind,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A failed crawl of a URL, as stored in Redis next to its cache entry.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UrlFailure {
+    pub kind: FailureKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the failure was recorded, in ms since the epoch.
+    pub failed_at: u64,
+}
+
+/// A crawled page as stored in Mongo and cached in the Redis hash.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredPage {
+    pub data: Value,
+    pub meta: PageMeta,
+}
+
+impl StoredPage {
+    pub fn new(data: impl Into<Value>) -> Self {
+        Self { data: data.into(), meta: PageMeta::default() }
+    }
+
+    pub fn crawled_at(mut self, crawled_at_ms: u64) -> Self {
+        self.meta.crawled_at = Some(crawled_at_ms);
+        self
+    }
+
+    pub fn with_meta(mut self, meta: PageMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    /// Hash fields that make up the cache entry, besides `fetched_at`.
+    /// String payloads are stored verbatim; anything else as JSON text tagged
+    /// with `data_format`.
+    pub fn to_cache_fields(&self) -> Vec<(&'static str, String)> {
+        let meta = &self.meta;
+        let mut fields = match &self.data {
+            Value::String(s) => vec![("data", s.clone())],
+            other => vec![("data", other.to_string()), ("data_format", "json".to_string())],
+        };
+        if let Some(code) = meta.status_code {
+            fields.push(("status_code", code.to_string()));
+        }
+        if let Some(content_type) = &meta.content_type {
+            fields.push(("content_type", content_type.clone()));
+        }
+        if let Some(final_url) = &meta.final_url {
+            fields.push(("final_url", final_url.clone()));
+        }
+        if let Some(at) = meta.crawled_at {
+            fields.push(("crawled_at", at.to_string()));
+        }
+        if let Some(etag) = &meta.etag {
+            fields.push(("etag", etag.clone()));
+        }
+        fields
+    }
+
+    /// Reads a page back from a cache hash. `None` when the hash holds no data,
+    /// e.g. when it only carries inflight markers.
+    pub fn from_cache_fields(hash: &HashMap<String, String>) -> Option<Self> {
+        let raw = hash.get("data")?;
+        let data = match hash.get("data_format").map(String::as_str) {
+            Some("json") => serde_json::from_str(raw).ok()?,
+            _ => Value::String(raw.clone()),
+        };
+        let meta = PageMeta {
+            status_code: hash.get("status_code").and_then(|v| v.parse().ok()),
+            content_type: hash.get("content_type").cloned(),
+            final_url: hash.get("final_url").cloned(),
+            crawled_at: hash.get("crawled_at").and_then(|v| v.parse().ok()),
+            etag: hash.get("etag").cloned(),
+        };
+        Some(Self { data, meta })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::ports::BoxError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckState {
+    Up,
+    Down,
+    Skipped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DependencyStatus {
+    pub status: CheckState,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn skipped() -> Self {
+        Self { status: CheckState::Skipped, latency_ms: 0, error: None }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub dependencies: BTreeMap<String, DependencyStatus>,
+}
+
+impl ReadinessReport {
+    /// Builds a report that is ready only when no dependency is down.
+    pub fn from_checks<I>(checks: I) -> Self
+    where
+        I: IntoIterator<Item = (&'static str, DependencyStatus)>,
+    {
+        let dependencies: BTreeMap<String, DependencyStatus> =
+            checks.into_iter().map(|(name, status)| (name.to_string(), status)).collect();
+        let ready = dependencies.values().all(|d| d.status != CheckState::Down);
+        Self { ready, dependencies }
+    }
+}
+
+/// Runs a single dependency check, treating both errors and timeouts as down.
+pub async fn probe<F>(check: F, timeout: Duration) -> DependencyStatus
+where
+    F: Future<Output = Result<(), BoxError>>,
+{
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(timeout, check).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match outcome {
+        Ok(Ok(())) => DependencyStatus { status: CheckState::Up, latency_ms, error: None },
+        Ok(Err(e)) => DependencyStatus { status: CheckState::Down, latency_ms, error: Some(e.to_string()) },
+        Err(_) => DependencyStatus {
+            status: CheckState::Down,
+            latency_ms,
+            error: Some(format!("timed out after {}ms", timeout.as_millis())),
+        },
+    }
+}
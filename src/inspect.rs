@@ -0,0 +1,89 @@
+use crate::domain::UrlFailure;
+use crate::policy::EffectivePolicy;
+use serde::Serialize;
+
+/// Everything the service knows about one URL, for answering "why isn't
+/// this URL showing up" without reading Redis and Mongo by hand.
+#[derive(Serialize, Clone, Debug)]
+pub struct UrlInspection {
+    pub url: String,
+    pub cache_key: String,
+    /// The TTLs and windows in effect for the URL.
+    pub policy: EffectivePolicy,
+    /// Whether the cache entry holds data, not just inflight markers.
+    pub cached: bool,
+    /// Time the cache entry has left, when it exists and the store can tell.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_ttl_ms: Option<u64>,
+    /// When the cached data was written, in ms since the epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<u64>,
+    /// Cached data past its soft TTL or crawled longer ago than `mongo_max_age_sec`.
+    pub stale: bool,
+    pub last_mongo_fetch: Window,
+    pub last_crawler_send: Window,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<UrlFailure>,
+    /// Whether page storage has a document for the URL.
+    pub stored: bool,
+    /// What a request for the URL would do right now.
+    pub decision: Decision,
+}
+
+/// The throttle windows of one URL, without its cached data or storage.
+#[derive(Serialize, Clone, Debug)]
+pub struct ThrottleState {
+    pub url: String,
+    pub cache_key: String,
+    pub policy: EffectivePolicy,
+    pub last_mongo_fetch: Window,
+    pub last_crawler_send: Window,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<UrlFailure>,
+}
+
+/// A throttle window opened by the marker timestamp `at`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    /// The marker, in ms since the epoch; `None` when it is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<u64>,
+    pub window_ms: u64,
+    /// Whether the window still holds requests back.
+    pub open: bool,
+    pub remaining_ms: u64,
+}
+
+impl Window {
+    pub fn new(at: u64, window_ms: u64, now_ms: u64) -> Self {
+        // Same comparison as `process`: the window closes once `window_ms`
+        // has passed, so a zero window is never open.
+        let open = at > 0 && now_ms.saturating_sub(at) < window_ms;
+        Self {
+            at: (at > 0).then_some(at),
+            window_ms,
+            open,
+            remaining_ms: if open { (at + window_ms).saturating_sub(now_ms) } else { 0 },
+        }
+    }
+}
+
+/// What `process` would do for a URL.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Decision {
+    /// Serve the cached data as it is.
+    ServeCached,
+    /// Serve the cached data marked stale; `refresh` when the request would
+    /// also re-read storage in the background.
+    ServeStale { refresh: bool },
+    /// Read the page from storage and cache it; `recrawl` when it is outdated
+    /// and would be sent to the crawler too.
+    ServeFromStorage { recrawl: bool },
+    /// Nothing to serve; report the recorded failure.
+    ReportFailure,
+    /// Nothing to serve; send the URL to the crawler.
+    SendToCrawler,
+    /// Nothing to serve; the crawler already has the URL and its window is open.
+    WaitForCrawler,
+}
@@ -3,6 +3,12 @@ pub mod ports;
 pub mod adapters;
 pub mod service;
 pub mod config;
+pub mod health;
+pub mod inspect;
+pub mod policy;
+pub mod metrics;
+pub mod clock;
+pub mod testkit;
 
 pub use domain::*;
 pub use ports::*;
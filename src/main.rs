@@ -1,70 +1,298 @@
-use actix_web::{App, HttpResponse, HttpServer, Responder, middleware::Logger, post, web};
+use actix_web::http::header::{
+    self, CacheControl, CacheDirective, ETag, EntityTag, HttpDate, IfNoneMatch, LastModified,
+};
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder, get, middleware::Logger, post, web};
+use clap::{Parser, Subcommand};
 use env_logger::Env;
-use std::env;
+use serde::Deserialize;
+use std::io;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use sha2::{Digest, Sha256};
 
-mod domain;
-mod ports;
-mod adapters;
-mod service;
-mod config;
+use groove_throttle::adapters::redis_backend::RedisBackend;
+use groove_throttle::adapters::storage_backend::StorageBackend;
+use groove_throttle::adapters::chaos::Chaos;
+use groove_throttle::adapters::crawler_backend::CrawlerBackend;
+use groove_throttle::service::{LoadReducerService, SingleUrl};
+use groove_throttle::config::{Config, ConfigHandle};
+use groove_throttle::domain::{DuplicateUrls, FailureReport, ResponseShape, UrlData};
+use groove_throttle::policy::PolicyEngine;
 
-use crate::adapters::redis_adapter::DeadpoolRedisAdapter;
-use crate::adapters::mongo_adapter::MongoAdapter;
-use crate::adapters::crawler_adapter::ReqwestCrawlerAdapter;
-use crate::service::LoadReducerService;
-use crate::config::Config;
+mod admin;
 
-type ConcreteService = LoadReducerService<DeadpoolRedisAdapter, MongoAdapter, ReqwestCrawlerAdapter>;
+#[derive(Parser)]
+#[command(version, about = "Redis/Mongo load reducer in front of the crawler")]
+struct Cli {
+    /// TOML config file. Environment variables override values from the file.
+    #[arg(long, short, env = "CONFIG_FILE", global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (the default).
+    Serve,
+    /// Print the effective configuration with secrets redacted, then exit.
+    PrintConfig,
+}
+
+// Every port sits behind a chaos wrapper, which passes calls straight through
+// unless faults are switched on via /admin/chaos.
+type ConcreteService = LoadReducerService<Chaos<RedisBackend>, Chaos<StorageBackend>, Chaos<CrawlerBackend>>;
+
+#[derive(Deserialize)]
+struct ApiQuery {
+    shape: Option<ResponseShape>,
+    duplicates: Option<DuplicateUrls>,
+}
 
 #[post("/api")]
-async fn handler(urls: web::Json<Vec<String>>, svc: web::Data<Arc<ConcreteService>>) -> impl Responder {
-    match svc.process(urls.0).await {
-        Ok(res) => HttpResponse::Ok().json(res),
+async fn handler(
+    urls: web::Json<Vec<String>>,
+    query: web::Query<ApiQuery>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let config = svc.config.load();
+    let shape = query.shape.unwrap_or(config.response_shape);
+    let duplicates = query.duplicates.unwrap_or(config.duplicate_urls);
+    match svc.process_with(urls.0, duplicates).await {
+        Ok(res) => match shape {
+            ResponseShape::Full => HttpResponse::Ok().json(res),
+            ResponseShape::Legacy => {
+                let legacy: Vec<_> = res.into_iter().filter_map(UrlData::into_legacy).collect();
+                HttpResponse::Ok().json(legacy)
+            }
+        },
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
 
+#[derive(Deserialize)]
+struct UrlQuery {
+    u: String,
+    shape: Option<ResponseShape>,
+}
+
+/// Single-URL lookup that browsers and CDNs can cache and revalidate.
+/// A URL still waiting for the crawler gets 202 with a `Retry-After`.
+#[get("/api/url")]
+async fn get_url(
+    req: HttpRequest,
+    query: web::Query<UrlQuery>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> HttpResponse {
+    let shape = query.shape.unwrap_or(svc.config.load().response_shape);
+    match svc.process_one(&query.u).await {
+        Ok(SingleUrl::Found { data, ttl }) => cacheable_response(&req, data, ttl, shape),
+        Ok(SingleUrl::Failed(data)) => HttpResponse::NotFound()
+            .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+            .json(data),
+        Ok(SingleUrl::Queued { retry_after }) => HttpResponse::Accepted()
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .insert_header((header::RETRY_AFTER, retry_after.as_millis().div_ceil(1000).max(1).to_string()))
+            .finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// `data` with validators: the ETag is a digest of the body, `Last-Modified`
+/// the crawl time, and `max-age` how long the entry stays fresh. Stale entries
+/// are being refreshed, so they must be revalidated right away.
+fn cacheable_response(req: &HttpRequest, data: UrlData, ttl: Option<Duration>, shape: ResponseShape) -> HttpResponse {
+    let max_age = if data.stale { Some(0) } else { ttl.map(|t| t.as_secs()) };
+    let last_modified = data.meta.crawled_at.map(|ms| HttpDate::from(UNIX_EPOCH + Duration::from_millis(ms)));
+    let body = match shape {
+        ResponseShape::Full => serde_json::to_vec(&data),
+        ResponseShape::Legacy => serde_json::to_vec(&data.into_legacy()),
+    };
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+    };
+    let digest = Sha256::digest(&body);
+    let etag = EntityTag::new_strong(digest[..16].iter().map(|b| format!("{:02x}", b)).collect());
+
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    let mut response = if unchanged { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    let freshness = match max_age {
+        Some(secs) => CacheDirective::MaxAge(u32::try_from(secs).unwrap_or(u32::MAX)),
+        None => CacheDirective::NoCache,
+    };
+    response.insert_header(CacheControl(vec![CacheDirective::Public, freshness]));
+    response.insert_header(ETag(etag));
+    if let Some(modified) = last_modified {
+        response.insert_header(LastModified(modified));
+    }
+    if unchanged {
+        response.finish()
+    } else {
+        response.content_type(header::ContentType::json()).body(body)
+    }
+}
+
+/// Ingest path for the crawler to report URLs it could not fetch. A recorded
+/// failure stops crawling of the URL, so reports need a token.
+#[post("/api/failures")]
+async fn report_failures(
+    auth: admin::IngestAuth,
+    reports: web::Json<Vec<FailureReport>>,
+    svc: web::Data<Arc<ConcreteService>>,
+) -> impl Responder {
+    let reports = reports.into_inner();
+    log::debug!("{} reported {} failed urls", auth.caller, reports.len());
+    record_failures(&svc, reports).await
+}
+
+async fn record_failures(svc: &ConcreteService, reports: Vec<FailureReport>) -> HttpResponse {
+    for report in reports {
+        if let Err(e) = svc.record_failure(&report.url, report.kind, report.reason).await {
+            return HttpResponse::InternalServerError().body(e.to_string());
+        }
+    }
+    HttpResponse::NoContent().finish()
+}
+
+/// Liveness: the process is up and serving HTTP. Dependencies are not touched.
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: every required dependency answered within the configured timeout.
+#[get("/readyz")]
+async fn readyz(svc: web::Data<Arc<ConcreteService>>) -> impl Responder {
+    let report = svc.readiness().await;
+    if report.ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// Prometheus scrape endpoint.
+#[get("/metrics")]
+async fn metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(groove_throttle::metrics::render())
+}
+
+fn startup_error(what: &str, e: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("{}: {}", what, e))
+}
+
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> io::Result<()> {
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
-    // Load environment variables
-    let redis_url = env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_string());
-    let mongo_url = env::var("MONGO_URL").unwrap_or("mongodb://localhost:27017".to_string());
-    let crawler_url = env::var("CRAWLER_URL").unwrap_or("http://localhost:8081/crawl".to_string());
+    let cli = Cli::parse();
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::PrintConfig => {
+            print!("{}", config.redacted().to_toml());
+            Ok(())
+        }
+        Command::Serve => serve(ConfigHandle::with_source(config, cli.config)).await,
+    }
+}
 
-    let config = Config::from_env();
+/// Reloads the config whenever the process receives SIGHUP.
+#[cfg(unix)]
+async fn reload_on_sighup(handle: ConfigHandle) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            log::warn!("cannot install SIGHUP handler, reload only via /admin/reload: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading config");
+        if let Err(e) = handle.reload() {
+            log::error!("config reload failed: {}", e);
+        }
+    }
+}
+
+/// Keeps the local policy rules in step with the shared copy in Redis.
+async fn refresh_policies(service: Arc<ConcreteService>) {
+    let config = service.config.load();
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(config.policy_refresh_ms));
+    loop {
+        interval.tick().await;
+        match service.policies.refresh_from(&service.redis, &config.policy_key).await {
+            Ok(count) => log::debug!("loaded {} policy rules", count),
+            Err(e) => log::warn!("policy refresh failed, keeping previous rules: {}", e),
+        }
+    }
+}
+
+async fn serve(config_handle: ConfigHandle) -> io::Result<()> {
+    let config = config_handle.load();
 
     // Setup Redis
-    let redis_cfg = deadpool_redis::Config::from_url(redis_url);
-    let redis_pool = redis_cfg
-        .create_pool(Some(deadpool_redis::Runtime::Tokio1))
-        .unwrap();
-
-    // Setup MongoDB
-    let mongo_options = mongodb::options::ClientOptions::parse(&mongo_url).await.unwrap();
-    let mongo_client = mongodb::Client::with_options(mongo_options).unwrap();
-    let db = mongo_client.database("my_database");
-    let coll = db.collection::<mongodb::bson::Document>("url_data");
-
-    // Create adapters
-    let redis_adapter = DeadpoolRedisAdapter { pool: redis_pool.clone() };
-    let mongo_adapter = MongoAdapter { coll: coll.clone() };
-    let crawler_adapter = ReqwestCrawlerAdapter { client: reqwest::Client::new(), url: crawler_url.clone() };
+    let redis_adapter =
+        RedisBackend::from_config(&config).map_err(|e| startup_error("failed to create Redis pool", e))?;
+
+    // Setup page storage
+    let storage_adapter =
+        StorageBackend::from_config(&config).await.map_err(|e| startup_error("failed to open page storage", e))?;
+
+    // Setup crawler dispatch
+    let crawler_adapter =
+        CrawlerBackend::from_config(&config).map_err(|e| startup_error("failed to set up crawler dispatch", e))?;
+
+    log::info!("effective config:\n{}", config.redacted().to_toml());
+
+    #[cfg(unix)]
+    actix_web::rt::spawn(reload_on_sighup(config_handle.clone()));
 
     // Create service with config
-    let service = LoadReducerService::new(redis_adapter, mongo_adapter, crawler_adapter, config);
+    let service = LoadReducerService::with_config_handle(
+        Chaos::new("redis", redis_adapter),
+        Chaos::new("storage", storage_adapter),
+        Chaos::new("crawler", crawler_adapter),
+        config_handle.clone(),
+    )
+    .with_policies(PolicyEngine::default());
+    let service = Arc::new(service);
+    actix_web::rt::spawn(refresh_policies(service.clone()));
 
-    let service_data: web::Data<Arc<ConcreteService>> = web::Data::new(Arc::new(service));
+    let service_data: web::Data<Arc<ConcreteService>> = web::Data::new(service);
+    let config_data = web::Data::new(config_handle);
 
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
             .app_data(service_data.clone())
+            .app_data(config_data.clone())
             .service(handler)
+            .service(get_url)
+            .service(report_failures)
+            .service(healthz)
+            .service(readyz)
+            .service(metrics)
+            .configure(admin::configure)
     })
-    .bind(("0.0.0.0", 8000))?
+    .bind(config.bind_addr.as_str())?
     .run()
     .await
 }
@@ -0,0 +1,23 @@
+//! Process-wide Prometheus metrics, exposed on `/metrics`.
+
+use prometheus::{Encoder, Histogram, TextEncoder, register_histogram};
+use std::sync::LazyLock;
+
+/// Uncompressed size divided by stored size, per compressed cache write.
+pub static CACHE_COMPRESSION_RATIO: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "groove_cache_compression_ratio",
+        "Ratio of uncompressed to stored size of compressed cache payloads",
+        vec![1.0, 1.5, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0, 16.0]
+    )
+    .expect("metric is registered once")
+});
+
+/// All registered metrics in the Prometheus text format.
+pub fn render() -> String {
+    let mut buf = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buf) {
+        log::warn!("failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buf).unwrap_or_default()
+}
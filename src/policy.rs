@@ -0,0 +1,179 @@
+use crate::config::Config;
+use crate::ports::{BoxError, PolicyStore};
+use arc_swap::ArcSwap;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Overrides for URLs matching every matcher that is set on the rule.
+///
+/// `host` matches the URL host exactly, or any subdomain when written as
+/// `*.example.com`. `path_prefix` matches the start of the URL path and
+/// `pattern` is a regex tested against the full URL.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    pub id: String,
+    /// Rules are tried from the highest priority down; the first match wins.
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl_sec: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_soft_ttl_sec: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mongo_prevent_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crawler_prevent_ms: Option<u64>,
+}
+
+impl PolicyRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("id must not be empty".to_string());
+        }
+        if self.host.is_none() && self.path_prefix.is_none() && self.pattern.is_none() {
+            return Err("at least one of host, path_prefix or pattern is required".to_string());
+        }
+        if let Some(pattern) = &self.pattern {
+            Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+        }
+        if self.cache_ttl_sec == Some(0) {
+            return Err("cache_ttl_sec must be greater than zero".to_string());
+        }
+        if let (Some(soft), Some(hard)) = (self.cache_soft_ttl_sec, self.cache_ttl_sec)
+            && soft >= hard
+        {
+            return Err("cache_soft_ttl_sec must be lower than cache_ttl_sec".to_string());
+        }
+        Ok(())
+    }
+
+    /// `validate`, plus a check of the TTL pair the rule ends up with once
+    /// unset values fall back to `config`, as in [`PolicyEngine::resolve`].
+    pub fn validate_with(&self, config: &Config) -> Result<(), String> {
+        self.validate()?;
+        let hard = self.cache_ttl_sec.unwrap_or(config.cache_ttl_sec);
+        let soft = self.cache_soft_ttl_sec.unwrap_or(config.cache_soft_ttl_sec);
+        if soft > 0 && soft >= hard {
+            return Err(format!(
+                "cache_soft_ttl_sec ({}) must be lower than cache_ttl_sec ({}), counting values taken from the \
+                 global config; set cache_soft_ttl_sec to 0 to disable it",
+                soft, hard
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// TTL and throttle windows that apply to one URL.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EffectivePolicy {
+    /// Id of the matching rule, `None` when the global config applies.
+    pub rule_id: Option<String>,
+    pub cache_ttl_sec: u64,
+    pub cache_soft_ttl_sec: u64,
+    pub mongo_prevent_ms: u64,
+    pub crawler_prevent_ms: u64,
+}
+
+impl EffectivePolicy {
+    pub fn name(&self) -> &str {
+        self.rule_id.as_deref().unwrap_or("default")
+    }
+}
+
+struct CompiledRule {
+    rule: PolicyRule,
+    host: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn compile(rule: PolicyRule) -> Result<Self, String> {
+        rule.validate()?;
+        let regex = rule.pattern.as_deref().map(Regex::new).transpose().map_err(|e| e.to_string())?;
+        let host = rule.host.as_deref().map(str::to_ascii_lowercase);
+        Ok(Self { rule, host, regex })
+    }
+
+    fn matches(&self, url: &str, host: &str, path: &str) -> bool {
+        let host_ok = match self.host.as_deref() {
+            None => true,
+            Some(wanted) => match wanted.strip_prefix("*.") {
+                Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+                None => host == wanted,
+            },
+        };
+        let path_ok = self.rule.path_prefix.as_deref().is_none_or(|prefix| path.starts_with(prefix));
+        let pattern_ok = self.regex.as_ref().is_none_or(|re| re.is_match(url));
+        host_ok && path_ok && pattern_ok
+    }
+}
+
+/// In-process view of the policy rules, shared by every request.
+///
+/// The rules themselves live in Redis (see [`PolicyStore`]) so that all
+/// instances agree; each instance refreshes its copy periodically and right
+/// after an admin edit.
+#[derive(Clone, Default)]
+pub struct PolicyEngine {
+    rules: Arc<ArcSwap<Vec<CompiledRule>>>,
+}
+
+impl PolicyEngine {
+    pub fn new(rules: Vec<PolicyRule>) -> Self {
+        let engine = Self::default();
+        engine.replace(rules);
+        engine
+    }
+
+    /// Swaps in a new rule set. Rules that fail validation are skipped.
+    pub fn replace(&self, rules: Vec<PolicyRule>) {
+        let mut compiled: Vec<CompiledRule> = rules
+            .into_iter()
+            .filter_map(|rule| {
+                let id = rule.id.clone();
+                CompiledRule::compile(rule)
+                    .map_err(|e| log::warn!("skipping invalid policy {:?}: {}", id, e))
+                    .ok()
+            })
+            .collect();
+        compiled.sort_by(|a, b| b.rule.priority.cmp(&a.rule.priority).then_with(|| a.rule.id.cmp(&b.rule.id)));
+        self.rules.store(Arc::new(compiled));
+    }
+
+    /// Current rules in evaluation order.
+    pub fn rules(&self) -> Vec<PolicyRule> {
+        self.rules.load().iter().map(|c| c.rule.clone()).collect()
+    }
+
+    /// Reloads the rules stored under `key`.
+    pub async fn refresh_from<S: PolicyStore>(&self, store: &S, key: &str) -> Result<usize, BoxError> {
+        let rules = store.load_policies(key).await?;
+        self.replace(rules);
+        Ok(self.rules.load().len())
+    }
+
+    /// The first matching rule's overrides on top of `config`.
+    pub fn resolve(&self, url: &str, config: &Config) -> EffectivePolicy {
+        let parsed = url::Url::parse(url).ok();
+        let host = parsed.as_ref().and_then(|u| u.host_str()).unwrap_or("").to_ascii_lowercase();
+        let path = parsed.as_ref().map(|u| u.path()).unwrap_or("");
+        let rules = self.rules.load();
+        let rule = rules.iter().find(|c| c.matches(url, &host, path)).map(|c| &c.rule);
+        EffectivePolicy {
+            rule_id: rule.map(|r| r.id.clone()),
+            cache_ttl_sec: rule.and_then(|r| r.cache_ttl_sec).unwrap_or(config.cache_ttl_sec),
+            cache_soft_ttl_sec: rule.and_then(|r| r.cache_soft_ttl_sec).unwrap_or(config.cache_soft_ttl_sec),
+            mongo_prevent_ms: rule.and_then(|r| r.mongo_prevent_ms).unwrap_or(config.mongo_prevent_ms),
+            crawler_prevent_ms: rule.and_then(|r| r.crawler_prevent_ms).unwrap_or(config.crawler_prevent_ms),
+        }
+    }
+}
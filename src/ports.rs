@@ -1,32 +1,128 @@
+use crate::domain::{StoredPage, UrlFailure};
+use crate::policy::PolicyRule;
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 pub trait RedisPort: Send + Sync {
-    async fn multi_hgetall(
+    fn multi_hgetall(
         &self,
         keys: &[String],
-    ) -> Result<Vec<HashMap<String, String>>, BoxError>;
-    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError>;
-    async fn write_cache_and_clear(
+    ) -> impl Future<Output = Result<Vec<HashMap<String, String>>, BoxError>> + Send;
+    fn hgetall(&self, key: &str) -> impl Future<Output = Result<HashMap<String, String>, BoxError>> + Send;
+    /// Replaces the hash with `page` and its `fetched_at` time (ms), which
+    /// drops the inflight fields, and sets the hard TTL.
+    fn write_cache_and_clear(
         &self,
         key: &str,
-        data: &str,
+        page: &StoredPage,
+        fetched_at: u64,
         cache_ttl: u64,
-    ) -> Result<(), BoxError>;
-    async fn set_inflight_fields(
+    ) -> impl Future<Output = Result<(), BoxError>> + Send;
+    fn set_inflight_fields(
         &self,
         key: &str,
         last_mongo: Option<u64>,
         last_crawler: Option<u64>,
         inflight_ttl: u64,
-    ) -> Result<(), BoxError>;
+    ) -> impl Future<Output = Result<(), BoxError>> + Send;
+    /// Like `set_inflight_fields` but leaves the key's TTL alone, for keys
+    /// that still hold cached data being refreshed in the background.
+    fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> impl Future<Output = Result<(), BoxError>> + Send;
+    /// Failure records stored under `keys`, in the same order; `None` where
+    /// nothing is recorded or the record has expired.
+    fn get_failures(
+        &self,
+        keys: &[String],
+    ) -> impl Future<Output = Result<Vec<Option<UrlFailure>>, BoxError>> + Send;
+    /// Stores `failure` under `key`, replacing any previous record, for `ttl` seconds.
+    fn set_failure(
+        &self,
+        key: &str,
+        failure: &UrlFailure,
+        ttl: u64,
+    ) -> impl Future<Output = Result<(), BoxError>> + Send;
+    /// Returns whether a record existed.
+    fn clear_failure(&self, key: &str) -> impl Future<Output = Result<bool, BoxError>> + Send;
+
+    /// Time `key` has left before it expires; `None` when it is missing, never
+    /// expires, or the store cannot tell.
+    fn remaining_ttl(&self, key: &str) -> impl Future<Output = Result<Option<Duration>, BoxError>> + Send {
+        let _ = key;
+        async { Ok(None) }
+    }
+
+    /// Every key starting with `prefix`, listed without blocking the store.
+    /// Stores that cannot list their keys may leave this unsupported.
+    fn scan_prefix(&self, prefix: &str) -> impl Future<Output = Result<Vec<String>, BoxError>> + Send {
+        let _ = prefix;
+        async { Err("this store cannot list its keys".into()) }
+    }
+
+    /// Every key made of the literal `prefix` followed by text matching
+    /// `pattern`, a Redis glob: `*`, `?`, `[...]` and `\` to escape.
+    fn scan_match(&self, prefix: &str, pattern: &str) -> impl Future<Output = Result<Vec<String>, BoxError>> + Send {
+        let _ = (prefix, pattern);
+        async { Err("this store cannot list its keys".into()) }
+    }
+
+    /// Deletes `keys`, returning how many of them existed.
+    fn delete_keys(&self, keys: &[String]) -> impl Future<Output = Result<u64, BoxError>> + Send {
+        let _ = keys;
+        async { Err("this store does not support deleting keys".into()) }
+    }
+
+    /// Checks that the backing store is reachable. Used by the readiness probe,
+    /// so stores that cannot tell are reported as not ready.
+    fn ping(&self) -> impl Future<Output = Result<(), BoxError>> + Send {
+        async { Err("this store has no readiness check".into()) }
+    }
 }
 
 pub trait MongoPort: Send + Sync {
-    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, String>, BoxError>;
+    fn find_by_urls(
+        &self,
+        urls: &[String],
+    ) -> impl Future<Output = Result<HashMap<String, StoredPage>, BoxError>> + Send;
+
+    /// Stores `pages` keyed by URL, replacing what is stored for those URLs.
+    /// Stores that are only read by the service may leave this unsupported.
+    fn save_pages(
+        &self,
+        pages: &HashMap<String, StoredPage>,
+    ) -> impl Future<Output = Result<(), BoxError>> + Send {
+        let _ = pages;
+        async { Err("this page store is read-only".into()) }
+    }
+
+    /// Checks that the backing store is reachable. Used by the readiness probe,
+    /// so stores that cannot tell are reported as not ready.
+    fn ping(&self) -> impl Future<Output = Result<(), BoxError>> + Send {
+        async { Err("this page store has no readiness check".into()) }
+    }
 }
 
 pub trait CrawlerPort: Send + Sync {
-    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError>;
+    fn send_batch(&self, urls: &[String]) -> impl Future<Output = Result<(), BoxError>> + Send;
+
+    /// Checks that the crawler endpoint answers at all. Used by the readiness
+    /// probe, so dispatchers that cannot tell are reported as not ready.
+    fn ping(&self) -> impl Future<Output = Result<(), BoxError>> + Send {
+        async { Err("this crawler dispatch has no readiness check".into()) }
+    }
+}
+
+/// Shared storage for policy rules, kept in a single hash under `key`.
+pub trait PolicyStore: Send + Sync {
+    fn load_policies(&self, key: &str) -> impl Future<Output = Result<Vec<PolicyRule>, BoxError>> + Send;
+    fn save_policy(&self, key: &str, rule: &PolicyRule) -> impl Future<Output = Result<(), BoxError>> + Send;
+    /// Returns whether a rule with that id existed.
+    fn delete_policy(&self, key: &str, id: &str) -> impl Future<Output = Result<bool, BoxError>> + Send;
 }
@@ -1,9 +1,28 @@
-use crate::domain::UrlData;
+use crate::domain::{DuplicateUrls, FailureKind, PurgeScope, StoredPage, UrlData, UrlFailure};
 use crate::ports::{BoxError, RedisPort, MongoPort, CrawlerPort};
-use crate::config::Config;
+use crate::config::{Config, ConfigHandle};
+use crate::health::{probe, DependencyStatus, ReadinessReport};
+use crate::inspect::{Decision, ThrottleState, UrlInspection, Window};
+use crate::policy::{EffectivePolicy, PolicyEngine};
 use std::collections::{HashMap, HashSet};
-use chrono::Utc;
+use crate::clock::{Clock, SystemClock};
+use std::sync::Arc;
+use std::time::Duration;
 
+/// What `process_one` found for a single URL.
+#[derive(Clone, Debug)]
+pub enum SingleUrl {
+    /// Data is available. `ttl` is how long it stays fresh, when known: what
+    /// its cache entry has left, capped at the end of the soft TTL.
+    Found { data: UrlData, ttl: Option<Duration> },
+    /// Nothing stored, only a recorded crawl failure.
+    Failed(UrlData),
+    /// Nothing stored yet and the crawler has the URL. A request after
+    /// `retry_after` looks in storage again.
+    Queued { retry_after: Duration },
+}
+
+#[derive(Clone)]
 pub struct LoadReducerService<R, M, C>
 where
     R: RedisPort,
@@ -13,7 +32,9 @@ where
     pub redis: R,
     pub mongo: M,
     pub crawler: C,
-    pub config: Config,
+    pub config: ConfigHandle,
+    pub policies: PolicyEngine,
+    pub clock: Arc<dyn Clock>,
 }
 
 impl<R, M, C> LoadReducerService<R, M, C>
@@ -23,63 +44,297 @@ where
     C: CrawlerPort,
 {
     pub fn new(redis: R, mongo: M, crawler: C, config: Config) -> Self {
-        Self { redis, mongo, crawler, config }
+        Self::with_config_handle(redis, mongo, crawler, ConfigHandle::new(config))
     }
 
-    pub async fn process(&self, urls: Vec<String>) -> Result<Vec<UrlData>, BoxError> {
-        let now_ms = Utc::now().timestamp_millis() as u64;
-        let cache_keys: Vec<String> = urls.iter().map(|u| format!("rcs::{}", u)).collect();
+    /// Builds the service around a shared handle so the config can be reloaded
+    /// while it is running.
+    pub fn with_config_handle(redis: R, mongo: M, crawler: C, config: ConfigHandle) -> Self {
+        Self { redis, mongo, crawler, config, policies: PolicyEngine::default(), clock: Arc::new(SystemClock) }
+    }
 
-        // Fetch hashes from Redis in one pipeline
-        let hashes = self.redis.multi_hgetall(&cache_keys).await?;
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
 
-        // Process hashes
-        let mut data_map: HashMap<String, String> = HashMap::new();
-        let mut to_query_mongo: HashSet<String> = HashSet::new();
-        let mut assumed_missing: Vec<String> = Vec::new();
+    /// Uses `policies` for per-URL TTL and throttle overrides.
+    pub fn with_policies(mut self, policies: PolicyEngine) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// Redis key holding the cached data and inflight markers for `url`.
+    pub fn cache_key(&self, url: &str) -> String {
+        self.key("", url)
+    }
+
+    /// Redis key holding the recorded crawl failure for `url`, if any.
+    pub fn failure_key(&self, url: &str) -> String {
+        self.key("failed::", url)
+    }
+
+    /// URL whose cache entry is stored under `key`; `None` for keys holding
+    /// something else, such as failure records.
+    pub fn url_from_cache_key(&self, key: &str) -> Option<String> {
+        let config = self.config.load();
+        let rest = key.strip_prefix(config.key_prefix.as_str())?;
+        if rest.starts_with("failed::") {
+            return None;
+        }
+        let url = if config.redis_hash_tags { rest.strip_prefix('{')?.strip_suffix('}')? } else { rest };
+        Some(url.to_string())
+    }
+
+    /// Cache keys of URLs starting with `url_prefix` start with this.
+    fn cache_key_prefix(&self, url_prefix: &str) -> String {
+        let config = self.config.load();
+        if config.redis_hash_tags {
+            format!("{}{{{}", config.key_prefix, url_prefix)
+        } else {
+            format!("{}{}", config.key_prefix, url_prefix)
+        }
+    }
+
+    fn key(&self, kind: &str, url: &str) -> String {
+        let config = self.config.load();
+        if config.redis_hash_tags {
+            format!("{}{}{{{}}}", config.key_prefix, kind, url)
+        } else {
+            format!("{}{}{}", config.key_prefix, kind, url)
+        }
+    }
+
+    /// Records a failed crawl of `url`. Until the negative TTL for `kind`
+    /// expires, `process` reports the URL as failed instead of sending it to
+    /// the crawler again.
+    pub async fn record_failure(
+        &self,
+        url: &str,
+        kind: FailureKind,
+        reason: Option<String>,
+    ) -> Result<UrlFailure, BoxError> {
+        let config = self.config.load();
+        let ttl = match kind {
+            FailureKind::Permanent => config.negative_ttl_permanent_sec,
+            FailureKind::Temporary => config.negative_ttl_temporary_sec,
+        };
+        let failure = UrlFailure { kind, reason, failed_at: self.clock.now_ms() };
+        self.redis.set_failure(&self.failure_key(url), &failure, ttl).await?;
+        Ok(failure)
+    }
+
+    /// Forgets a recorded failure so the URL is crawled again on its next
+    /// request. Returns whether one was recorded.
+    pub async fn clear_failure(&self, url: &str) -> Result<bool, BoxError> {
+        self.redis.clear_failure(&self.failure_key(url)).await
+    }
+
+    /// Drops the cached data and inflight markers of the URLs in `scope`, so
+    /// their next request goes to storage and, if needed, the crawler without
+    /// waiting out any window. Recorded failures are kept. Returns the URLs
+    /// whose entries were dropped.
+    pub async fn purge(&self, scope: &PurgeScope) -> Result<Vec<String>, BoxError> {
+        let urls = self.purge_targets(scope).await?;
+        let keys: Vec<String> = urls.iter().map(|u| self.cache_key(u)).collect();
+        let deleted = self.redis.delete_keys(&keys).await?;
+        if matches!(scope, PurgeScope::Url(_)) && deleted == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(urls)
+    }
+
+    /// URLs whose cache entries `purge` would drop; an exact URL is listed
+    /// whether or not it is cached.
+    pub async fn purge_targets(&self, scope: &PurgeScope) -> Result<Vec<String>, BoxError> {
+        Ok(match scope {
+            PurgeScope::Url(url) => vec![url.clone()],
+            PurgeScope::Prefix(prefix) => self.cached_urls_with_prefix(prefix).await?,
+            PurgeScope::Pattern(pattern) => self.cached_urls_matching(pattern).await?,
+            PurgeScope::Host(host) => {
+                // Scan all web URLs rather than one host prefix, so spellings
+                // of the host in another case or with credentials are found too.
+                let host = host.trim();
+                self.cached_urls_with_prefix("http")
+                    .await?
+                    .into_iter()
+                    .filter(|url| {
+                        url::Url::parse(url)
+                            .ok()
+                            .is_some_and(|u| u.host_str().is_some_and(|h| h.eq_ignore_ascii_case(host)))
+                    })
+                    .collect()
+            }
+        })
+    }
+
+    async fn cached_urls_with_prefix(&self, prefix: &str) -> Result<Vec<String>, BoxError> {
+        let keys = self.redis.scan_prefix(&self.cache_key_prefix(prefix)).await?;
+        Ok(keys.iter().filter_map(|key| self.url_from_cache_key(key)).collect())
+    }
+
+    /// URLs of cache entries matching the glob `pattern`; only the URL part
+    /// of the key is matched against it.
+    async fn cached_urls_matching(&self, pattern: &str) -> Result<Vec<String>, BoxError> {
+        // a hash-tagged key closes the tag after the URL
+        let pattern = if self.config.load().redis_hash_tags { format!("{}}}", pattern) } else { pattern.to_string() };
+        let keys = self.redis.scan_match(&self.cache_key_prefix(""), &pattern).await?;
+        Ok(keys.iter().filter_map(|key| self.url_from_cache_key(key)).collect())
+    }
 
-        for (i, hash) in hashes.into_iter().enumerate() {
-            let url = urls[i].clone();
-            if let Some(data) = hash.get("data") {
-                data_map.insert(url, data.clone());
+    /// Caches what storage has for `urls` with their policy TTL, as a cache
+    /// miss would. URLs storage lacks are left alone. Returns the URLs cached.
+    pub async fn warm(&self, urls: &[String]) -> Result<Vec<String>, BoxError> {
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let found = self.mongo.find_by_urls(urls).await?;
+        let mut cached = Vec::new();
+        let mut seen = HashSet::new();
+        for url in urls {
+            let Some(page) = found.get(url) else { continue };
+            if !seen.insert(url.as_str()) {
                 continue;
             }
+            let policy = self.policies.resolve(url, &config);
+            self.redis.write_cache_and_clear(&self.cache_key(url), page, now_ms, policy.cache_ttl_sec).await?;
+            cached.push(url.clone());
+        }
+        Ok(cached)
+    }
 
-            let last_mongo_str = hash.get("last_mongo_fetch").cloned().unwrap_or_default();
-            let last_mongo: u64 = last_mongo_str.parse().unwrap_or(0);
-            if now_ms.saturating_sub(last_mongo) >= self.config.mongo_prevent_ms {
-                to_query_mongo.insert(url);
+    /// Sends `urls` to the crawler now, regardless of `crawler_prevent_ms`
+    /// and recorded failures, and restarts their crawler window. Returns the
+    /// distinct URLs sent.
+    pub async fn force_crawl(&self, urls: &[String]) -> Result<Vec<String>, BoxError> {
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let urls: Vec<String> = urls.iter().filter(|u| seen.insert(u.as_str())).cloned().collect();
+        if urls.is_empty() {
+            return Ok(urls);
+        }
+        let keys: Vec<String> = urls.iter().map(|u| self.cache_key(u)).collect();
+        let hashes = self.redis.multi_hgetall(&keys).await?;
+        self.crawler.send_batch(&urls).await?;
+        for (key, hash) in keys.iter().zip(&hashes) {
+            // Keys with cached data keep their TTL, like during a refresh.
+            if StoredPage::from_cache_fields(hash).is_some() {
+                self.redis.mark_refresh(key, None, Some(now_ms)).await?;
             } else {
-                assumed_missing.push(url);
+                self.redis.set_inflight_fields(key, None, Some(now_ms), config.inflight_ttl_sec).await?;
             }
         }
+        Ok(urls)
+    }
 
-        // Add URLs not present in Redis at all
-        let all_urls_set: HashSet<String> = urls.iter().cloned().collect();
-        let processed_urls: HashSet<String> = data_map
-            .keys()
-            .cloned()
-            .chain(assumed_missing.iter().cloned())
+    async fn failures_for(&self, urls: &[String]) -> Result<HashMap<String, UrlFailure>, BoxError> {
+        if urls.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let keys: Vec<String> = urls.iter().map(|u| self.failure_key(u)).collect();
+        let failures = self.redis.get_failures(&keys).await?;
+        Ok(urls
+            .iter()
+            .zip(failures)
+            .filter_map(|(url, failure)| Some((url.clone(), failure?)))
+            .collect())
+    }
+
+    /// Answers `urls`, repeated ones as configured by `duplicate_urls`.
+    pub async fn process(&self, urls: Vec<String>) -> Result<Vec<UrlData>, BoxError>
+    where
+        R: Clone + 'static,
+        M: Clone + 'static,
+        C: Clone + 'static,
+    {
+        let duplicates = self.config.load().duplicate_urls;
+        self.process_with(urls, duplicates).await
+    }
+
+    /// Answers `requested` with `duplicates` deciding how repeated URLs are
+    /// answered. Lookups, inflight markers and crawler dispatch happen once
+    /// per distinct URL regardless.
+    pub async fn process_with(
+        &self,
+        requested: Vec<String>,
+        duplicates: DuplicateUrls,
+    ) -> Result<Vec<UrlData>, BoxError>
+    where
+        R: Clone + 'static,
+        M: Clone + 'static,
+        C: Clone + 'static,
+    {
+        // One snapshot per request: a concurrent reload only affects later requests.
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let urls: Vec<String> = requested.iter().filter(|u| seen.insert(u.as_str())).cloned().collect();
+        let cache_keys: Vec<String> = urls.iter().map(|u| self.cache_key(u)).collect();
+        let policies: HashMap<String, EffectivePolicy> = urls
+            .iter()
+            .map(|u| {
+                let policy = self.policies.resolve(u, &config);
+                log::debug!("policy {} applies to {}", policy.name(), u);
+                (u.clone(), policy)
+            })
             .collect();
-        for url in all_urls_set.difference(&processed_urls) {
-            to_query_mongo.insert(url.clone());
+
+        // Fetch hashes from Redis in one pipeline
+        let hashes = self.redis.multi_hgetall(&cache_keys).await?;
+
+        // Process hashes; URLs without a hash are treated like empty ones
+        let mut data_map: HashMap<String, StoredPage> = HashMap::new();
+        let mut to_query_mongo: HashSet<String> = HashSet::new();
+        let mut assumed_missing: Vec<String> = Vec::new();
+        let mut stale: HashSet<String> = HashSet::new();
+        let mut to_refresh: HashSet<String> = HashSet::new();
+        let mut last_crawler_sends: HashMap<String, u64> = HashMap::new();
+
+        let empty = HashMap::new();
+        for (i, url) in urls.iter().enumerate() {
+            let hash = hashes.get(i).unwrap_or(&empty);
+            let policy = &policies[url];
+            let last_mongo = ms_field(hash, "last_mongo_fetch");
+            last_crawler_sends.insert(url.clone(), ms_field(hash, "last_crawler_send"));
+
+            if let Some(page) = StoredPage::from_cache_fields(hash) {
+                if is_past_soft_ttl(hash, policy, now_ms) || is_outdated(&page, &config, now_ms) {
+                    stale.insert(url.clone());
+                    if now_ms.saturating_sub(last_mongo) >= policy.mongo_prevent_ms {
+                        to_refresh.insert(url.clone());
+                    }
+                }
+                data_map.insert(url.clone(), page);
+            } else if now_ms.saturating_sub(last_mongo) >= policy.mongo_prevent_ms {
+                to_query_mongo.insert(url.clone());
+            } else {
+                assumed_missing.push(url.clone());
+            }
         }
 
         // Query Mongo in batch
-        let mut mongo_found: HashMap<String, String> = HashMap::new();
+        let mut mongo_found: HashMap<String, StoredPage> = HashMap::new();
         if !to_query_mongo.is_empty() {
             let to_query_vec: Vec<String> = to_query_mongo.iter().cloned().collect();
             let found = self.mongo.find_by_urls(&to_query_vec).await?;
             mongo_found = found;
         }
 
-        // Process mongo results and write cache
-        for (url, data) in mongo_found.into_iter() {
-            let key = format!("rcs::{}", url);
+        // Process mongo results and write cache; outdated pages are served
+        // but queued for recrawl
+        let mut to_recrawl: Vec<String> = Vec::new();
+        for (url, page) in mongo_found.into_iter() {
+            let key = self.cache_key(&url);
+            let cache_ttl = policies.get(&url).map_or(config.cache_ttl_sec, |p| p.cache_ttl_sec);
             self.redis
-                .write_cache_and_clear(&key, &data, self.config.cache_ttl_sec)
+                .write_cache_and_clear(&key, &page, now_ms, cache_ttl)
                 .await?;
-            data_map.insert(url, data);
+            if is_outdated(&page, &config, now_ms) {
+                stale.insert(url.clone());
+                to_recrawl.push(url.clone());
+            }
+            data_map.insert(url, page);
         }
 
         // Build missing lists
@@ -94,14 +349,18 @@ where
             .cloned()
             .collect::<Vec<_>>();
 
+        // URLs with a recorded failure are reported as such and not dispatched
+        let candidates: Vec<String> = all_missing.iter().chain(to_recrawl.iter()).cloned().collect();
+        let failures = self.failures_for(&candidates).await?;
+
         // Determine which to send to crawler and update inflight fields
         let mut to_crawler: Vec<String> = Vec::new();
         for url in all_missing.iter() {
-            let key = format!("rcs::{}", url);
+            let key = self.cache_key(url);
             let hash = self.redis.hgetall(&key).await?;
-            let last_crawler_str = hash.get("last_crawler_send").cloned().unwrap_or_default();
-            let last_crawler: u64 = last_crawler_str.parse().unwrap_or(0);
-            let should_send_crawler = now_ms.saturating_sub(last_crawler) >= self.config.crawler_prevent_ms;
+            let last_crawler = ms_field(&hash, "last_crawler_send");
+            let should_send_crawler = !failures.contains_key(url)
+                && now_ms.saturating_sub(last_crawler) >= policies[url].crawler_prevent_ms;
 
             let is_queried_not_found = queried_not_found.contains(url);
             let last_mongo = if is_queried_not_found { Some(now_ms) } else { None };
@@ -113,21 +372,262 @@ where
 
             if last_mongo.is_some() || last_crawler_opt.is_some() {
                 self.redis
-                    .set_inflight_fields(&key, last_mongo, last_crawler_opt, self.config.inflight_ttl_sec)
+                    .set_inflight_fields(&key, last_mongo, last_crawler_opt, config.inflight_ttl_sec)
                     .await?;
             }
         }
 
+        // Outdated pages now hold cached data, so their crawler marker is set
+        // without touching the TTL. Writing the cache cleared it, so the window
+        // is judged on the marker read at the start of the request.
+        for url in to_recrawl.iter() {
+            let key = self.cache_key(url);
+            let last_crawler = last_crawler_sends.get(url).copied().unwrap_or(0);
+            if !failures.contains_key(url) && now_ms.saturating_sub(last_crawler) >= policies[url].crawler_prevent_ms {
+                to_crawler.push(url.clone());
+                self.redis.mark_refresh(&key, None, Some(now_ms)).await?;
+            } else if last_crawler > 0 {
+                self.redis.mark_refresh(&key, None, Some(last_crawler)).await?;
+            }
+        }
+
         if !to_crawler.is_empty() {
             self.crawler.send_batch(&to_crawler).await?;
         }
 
+        // Claim the refresh of stale entries before answering, so concurrent
+        // requests within `mongo_prevent_ms` leave it to this one
+        if !to_refresh.is_empty() {
+            let to_refresh: Vec<String> = to_refresh.into_iter().collect();
+            for url in &to_refresh {
+                self.redis.mark_refresh(&self.cache_key(url), Some(now_ms), None).await?;
+            }
+            self.spawn_refresh(to_refresh);
+        }
+
         // Build response preserving order
-        let response: Vec<UrlData> = urls
+        let answered = match duplicates {
+            DuplicateUrls::Echo => requested,
+            DuplicateUrls::Dedupe => urls,
+        };
+        let response: Vec<UrlData> = answered
             .into_iter()
-            .filter_map(|url| data_map.get(&url).map(|data| UrlData { url: url.clone(), data: data.clone() }))
+            .filter_map(|url| {
+                let page = data_map.get(&url);
+                let failure = failures.get(&url).cloned();
+                if page.is_none() && failure.is_none() {
+                    return None;
+                }
+                Some(UrlData {
+                    stale: stale.contains(&url),
+                    data: page.map(|p| p.data.clone()).unwrap_or_default(),
+                    meta: page.map(|p| p.meta.clone()).unwrap_or_default(),
+                    url,
+                    failure,
+                })
+            })
             .collect();
 
         Ok(response)
     }
+
+    /// `process` for a single URL, telling apart data, a recorded failure and
+    /// a URL still waiting for the crawler.
+    pub async fn process_one(&self, url: &str) -> Result<SingleUrl, BoxError>
+    where
+        R: Clone + 'static,
+        M: Clone + 'static,
+        C: Clone + 'static,
+    {
+        let Some(data) = self.process_with(vec![url.to_string()], DuplicateUrls::Dedupe).await?.pop() else {
+            let policy = self.policies.resolve(url, &self.config.load());
+            return Ok(SingleUrl::Queued { retry_after: Duration::from_millis(policy.mongo_prevent_ms) });
+        };
+        if data.data.is_null() && data.failure.is_some() {
+            return Ok(SingleUrl::Failed(data));
+        }
+        let cache_key = self.cache_key(url);
+        let mut ttl = self.redis.remaining_ttl(&cache_key).await?;
+        let policy = self.policies.resolve(url, &self.config.load());
+        if policy.cache_soft_ttl_sec > 0 {
+            let hash = self.redis.hgetall(&cache_key).await?;
+            if let Some(left) = soft_ttl_left(&hash, &policy, self.clock.now_ms()) {
+                ttl = Some(ttl.map_or(left, |hard| hard.min(left)));
+            }
+        }
+        Ok(SingleUrl::Found { data, ttl })
+    }
+
+    fn spawn_refresh(&self, urls: Vec<String>)
+    where
+        R: Clone + 'static,
+        M: Clone + 'static,
+        C: Clone + 'static,
+    {
+        let service = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = service.refresh(&urls).await {
+                log::warn!("background refresh of {} urls failed: {}", urls.len(), e);
+            }
+        });
+    }
+
+    /// Re-reads `urls` from Mongo and rewrites their cache entries. URLs Mongo
+    /// no longer has, or only has in an outdated version, are handed to the
+    /// crawler subject to `crawler_prevent_ms`; data already cached for them
+    /// stays until the hard TTL.
+    pub async fn refresh(&self, urls: &[String]) -> Result<(), BoxError> {
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let keys: Vec<String> = urls.iter().map(|u| self.cache_key(u)).collect();
+
+        // Crawler markers must be read before the cache write clears them
+        let hashes = self.redis.multi_hgetall(&keys).await?;
+        let found = self.mongo.find_by_urls(urls).await?;
+        let failures = self.failures_for(urls).await?;
+
+        let mut to_crawler: Vec<String> = Vec::new();
+        for (i, url) in urls.iter().enumerate() {
+            let key = &keys[i];
+            let policy = self.policies.resolve(url, &config);
+            let last_crawler = hashes.get(i).map_or(0, |h| ms_field(h, "last_crawler_send"));
+            let page = found.get(url);
+            if let Some(page) = page {
+                self.redis
+                    .write_cache_and_clear(key, page, now_ms, policy.cache_ttl_sec)
+                    .await?;
+            }
+            let needs_crawl = page.is_none_or(|p| is_outdated(p, &config, now_ms));
+            if !needs_crawl {
+                continue;
+            }
+            if !failures.contains_key(url) && now_ms.saturating_sub(last_crawler) >= policy.crawler_prevent_ms {
+                self.redis.mark_refresh(key, None, Some(now_ms)).await?;
+                to_crawler.push(url.clone());
+            } else if page.is_some() && last_crawler > 0 {
+                self.redis.mark_refresh(key, None, Some(last_crawler)).await?;
+            }
+        }
+        if !to_crawler.is_empty() {
+            self.crawler.send_batch(&to_crawler).await?;
+        }
+        Ok(())
+    }
+
+    /// The throttle markers and recorded failure of `url`, read from Redis only.
+    pub async fn throttle_state(&self, url: &str) -> Result<ThrottleState, BoxError> {
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let policy = self.policies.resolve(url, &config);
+        let cache_key = self.cache_key(url);
+        let hash = self.redis.hgetall(&cache_key).await?;
+        let failure = self.failures_for(&[url.to_string()]).await?.remove(url);
+        Ok(ThrottleState {
+            url: url.to_string(),
+            last_mongo_fetch: Window::new(ms_field(&hash, "last_mongo_fetch"), policy.mongo_prevent_ms, now_ms),
+            last_crawler_send: Window::new(ms_field(&hash, "last_crawler_send"), policy.crawler_prevent_ms, now_ms),
+            cache_key,
+            policy,
+            failure,
+        })
+    }
+
+    /// Reads what Redis and storage hold for `url` and works out what
+    /// `process` would do with it, without changing anything.
+    pub async fn inspect(&self, url: &str) -> Result<UrlInspection, BoxError> {
+        let config = self.config.load();
+        let now_ms = self.clock.now_ms();
+        let policy = self.policies.resolve(url, &config);
+        let cache_key = self.cache_key(url);
+        let urls = [url.to_string()];
+
+        let hash = self.redis.hgetall(&cache_key).await?;
+        let cache_ttl = self.redis.remaining_ttl(&cache_key).await?;
+        let failure = self.failures_for(&urls).await?.remove(url);
+        let stored = self.mongo.find_by_urls(&urls).await?.remove(url);
+
+        let cached = StoredPage::from_cache_fields(&hash);
+        let last_mongo_fetch = Window::new(ms_field(&hash, "last_mongo_fetch"), policy.mongo_prevent_ms, now_ms);
+        let last_crawler_send = Window::new(ms_field(&hash, "last_crawler_send"), policy.crawler_prevent_ms, now_ms);
+        let stale = cached
+            .as_ref()
+            .is_some_and(|page| is_past_soft_ttl(&hash, &policy, now_ms) || is_outdated(page, &config, now_ms));
+        let can_crawl = failure.is_none() && !last_crawler_send.open;
+        let decision = match (&cached, &stored) {
+            (Some(_), _) if !stale => Decision::ServeCached,
+            (Some(_), _) => Decision::ServeStale { refresh: !last_mongo_fetch.open },
+            // Storage is only consulted once the Mongo window has passed.
+            (None, Some(page)) if !last_mongo_fetch.open => {
+                Decision::ServeFromStorage { recrawl: is_outdated(page, &config, now_ms) && can_crawl }
+            }
+            _ if failure.is_some() => Decision::ReportFailure,
+            _ if can_crawl => Decision::SendToCrawler,
+            _ => Decision::WaitForCrawler,
+        };
+
+        Ok(UrlInspection {
+            url: url.to_string(),
+            cache_key,
+            cached: cached.is_some(),
+            cache_ttl_ms: cache_ttl.map(|t| t.as_millis() as u64),
+            fetched_at: hash.get("fetched_at").and_then(|v| v.parse().ok()),
+            stale,
+            last_mongo_fetch,
+            last_crawler_send,
+            failure,
+            stored: stored.is_some(),
+            decision,
+            policy,
+        })
+    }
+
+    /// Probes every dependency concurrently, each bounded by `readiness_timeout_ms`.
+    /// The crawler is only probed when `readiness_check_crawler` is enabled.
+    pub async fn readiness(&self) -> ReadinessReport {
+        let config = self.config.load();
+        let timeout = Duration::from_millis(config.readiness_timeout_ms);
+        let crawler_check = async {
+            if config.readiness_check_crawler {
+                probe(self.crawler.ping(), timeout).await
+            } else {
+                DependencyStatus::skipped()
+            }
+        };
+        let (redis, mongo, crawler) = futures::join!(
+            probe(self.redis.ping(), timeout),
+            probe(self.mongo.ping(), timeout),
+            crawler_check,
+        );
+        ReadinessReport::from_checks([("redis", redis), ("mongo", mongo), ("crawler", crawler)])
+    }
+}
+
+/// Millisecond timestamp stored in a hash field, 0 when absent or unreadable.
+fn ms_field(hash: &HashMap<String, String>, field: &str) -> u64 {
+    hash.get(field).and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+/// Whether a cache entry is older than the soft TTL. Entries cached before
+/// `fetched_at` existed have no known age and count as fresh.
+fn is_past_soft_ttl(hash: &HashMap<String, String>, policy: &EffectivePolicy, now_ms: u64) -> bool {
+    let fetched_at = hash.get("fetched_at").and_then(|v| v.parse::<u64>().ok());
+    fetched_at.is_some_and(|at| policy.cache_soft_ttl_sec > 0 && now_ms.saturating_sub(at) > policy.cache_soft_ttl_sec * 1000)
+}
+
+/// Time until a cache entry passes the soft TTL, `None` when its age is
+/// unknown.
+fn soft_ttl_left(hash: &HashMap<String, String>, policy: &EffectivePolicy, now_ms: u64) -> Option<Duration> {
+    let fetched_at = hash.get("fetched_at").and_then(|v| v.parse::<u64>().ok())?;
+    let fresh_until = fetched_at.saturating_add(policy.cache_soft_ttl_sec * 1000);
+    Some(Duration::from_millis(fresh_until.saturating_sub(now_ms)))
+}
+
+/// Whether the page was crawled longer ago than `mongo_max_age_sec`. Pages
+/// without a crawl time are never considered outdated.
+fn is_outdated(page: &StoredPage, config: &Config, now_ms: u64) -> bool {
+    config.mongo_max_age_sec > 0
+        && page
+            .meta
+            .crawled_at
+            .is_some_and(|at| now_ms.saturating_sub(at) > config.mongo_max_age_sec * 1000)
 }
@@ -0,0 +1,242 @@
+//! Conformance checks any port implementation can run against itself.
+//!
+//! Each check drives the implementation through the behavior the service
+//! relies on and panics with a description of the first deviation, so they
+//! are meant to be called from `#[tokio::test]` functions:
+//!
+//! ```ignore
+//! let redis = MemoryRedisAdapter::new(1_000).with_clock(clock.clone());
+//! testkit::redis_port(&redis).await;
+//! testkit::redis_port_ttl(&redis, |d| { clock.advance(d); async {} }).await;
+//! ```
+//!
+//! Keys and URLs are namespaced per run, so the checks can share a store
+//! with other data.
+
+use crate::domain::{FailureKind, PageMeta, StoredPage, UrlFailure};
+use crate::ports::{CrawlerPort, MongoPort, RedisPort};
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A prefix no earlier run in the same store has used.
+fn namespace(kind: &str) -> String {
+    static RUN: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    format!("testkit::{}::{}-{}::", kind, nanos, RUN.fetch_add(1, Ordering::Relaxed))
+}
+
+/// A page with every metadata field set and a structured payload.
+pub fn sample_page() -> StoredPage {
+    StoredPage::new(json!({ "title": "sample", "links": ["a", "b"], "words": 42 })).with_meta(PageMeta {
+        status_code: Some(200),
+        content_type: Some("text/html; charset=utf-8".to_string()),
+        final_url: Some("https://example.com/final".to_string()),
+        crawled_at: Some(1_700_000_000_000),
+        etag: Some("\"abc\"".to_string()),
+    })
+}
+
+/// Hash round-trips, inflight markers and failure records on a `RedisPort`.
+pub async fn redis_port<R: RedisPort>(redis: &R) {
+    let ns = namespace("redis");
+    let key = |name: &str| format!("{}{}", ns, name);
+
+    // cache entries round-trip through the hash
+    let page = sample_page();
+    redis.write_cache_and_clear(&key("page"), &page, 123, 600).await.expect("write_cache_and_clear");
+    let hash = redis.hgetall(&key("page")).await.expect("hgetall");
+    assert_eq!(StoredPage::from_cache_fields(&hash), Some(page.clone()), "cached page differs from the one written");
+    assert_eq!(hash.get("fetched_at").map(String::as_str), Some("123"), "fetched_at not stored");
+    let plain = StoredPage::new("<html>plain</html>");
+    redis.write_cache_and_clear(&key("plain"), &plain, 1, 600).await.expect("write_cache_and_clear");
+    assert_eq!(
+        StoredPage::from_cache_fields(&redis.hgetall(&key("plain")).await.expect("hgetall")),
+        Some(plain),
+        "string payload not stored verbatim"
+    );
+
+    // missing keys read as empty hashes, in request order
+    assert!(redis.hgetall(&key("missing")).await.expect("hgetall").is_empty(), "missing key is not empty");
+    let keys = vec![key("missing"), key("page"), key("missing-too")];
+    let hashes = redis.multi_hgetall(&keys).await.expect("multi_hgetall");
+    assert_eq!(hashes.len(), keys.len(), "multi_hgetall must answer every key");
+    assert!(hashes[0].is_empty() && hashes[2].is_empty(), "missing keys must read as empty hashes");
+    assert_eq!(hashes[1], hash, "multi_hgetall and hgetall disagree");
+    assert!(redis.multi_hgetall(&[]).await.expect("multi_hgetall of nothing").is_empty());
+
+    // inflight markers are set, extended and cleared by a cache write
+    redis.set_inflight_fields(&key("inflight"), None, None, 600).await.expect("set_inflight_fields");
+    assert!(redis.hgetall(&key("inflight")).await.expect("hgetall").is_empty(), "no markers must write nothing");
+    redis.set_inflight_fields(&key("inflight"), Some(10), Some(20), 600).await.expect("set_inflight_fields");
+    redis.mark_refresh(&key("inflight"), Some(11), None).await.expect("mark_refresh");
+    let markers = redis.hgetall(&key("inflight")).await.expect("hgetall");
+    assert_eq!(markers.get("last_mongo_fetch").map(String::as_str), Some("11"), "mark_refresh must overwrite");
+    assert_eq!(markers.get("last_crawler_send").map(String::as_str), Some("20"), "unrelated marker lost");
+    redis.write_cache_and_clear(&key("inflight"), &page, 30, 600).await.expect("write_cache_and_clear");
+    let cleared = redis.hgetall(&key("inflight")).await.expect("hgetall");
+    assert!(
+        !cleared.contains_key("last_mongo_fetch") && !cleared.contains_key("last_crawler_send"),
+        "cache write must clear inflight markers"
+    );
+
+    // refresh markers sit next to cached data without replacing it
+    redis.mark_refresh(&key("page"), None, Some(40)).await.expect("mark_refresh");
+    let refreshing = redis.hgetall(&key("page")).await.expect("hgetall");
+    assert_eq!(StoredPage::from_cache_fields(&refreshing), Some(page), "mark_refresh must keep the cached page");
+    assert_eq!(refreshing.get("last_crawler_send").map(String::as_str), Some("40"));
+
+    // failure records
+    let failure = UrlFailure { kind: FailureKind::Permanent, reason: Some("404".to_string()), failed_at: 5 };
+    redis.set_failure(&key("failed"), &failure, 600).await.expect("set_failure");
+    let failures = redis.get_failures(&[key("failed"), key("ok")]).await.expect("get_failures");
+    assert_eq!(failures, vec![Some(failure), None], "failure records must come back in key order");
+    assert!(redis.get_failures(&[]).await.expect("get_failures of nothing").is_empty());
+    assert!(redis.clear_failure(&key("failed")).await.expect("clear_failure"), "clearing a record reports it");
+    assert!(!redis.clear_failure(&key("failed")).await.expect("clear_failure"), "nothing left to clear");
+
+    // keys are listed by literal prefix, even one with glob characters
+    for name in ["scan/a", "scan/b", "scan*/c", "scanned"] {
+        redis.set_inflight_fields(&key(name), Some(1), None, 600).await.expect("set_inflight_fields");
+    }
+    let scan = |prefix: String| async move {
+        let mut keys = redis.scan_prefix(&prefix).await.expect("scan_prefix");
+        keys.sort();
+        keys
+    };
+    assert_eq!(scan(key("scan/")).await, vec![key("scan/a"), key("scan/b")]);
+    assert_eq!(scan(key("scan*")).await, vec![key("scan*/c")], "glob characters must match literally");
+    // and by a glob after a literal prefix
+    let matched = |prefix: String, pattern: &'static str| async move {
+        let mut keys = redis.scan_match(&prefix, pattern).await.expect("scan_match");
+        keys.sort();
+        keys
+    };
+    assert_eq!(matched(key("scan"), "/?").await, vec![key("scan/a"), key("scan/b")]);
+    assert_eq!(matched(key("scan"), "[*]/*").await, vec![key("scan*/c")]);
+    assert_eq!(matched(key("scan"), "[^/*]*").await, vec![key("scanned")]);
+    assert_eq!(matched(key("scan"), "/[a-a]").await, vec![key("scan/a")]);
+    assert_eq!(matched(key("scan*"), "*").await, vec![key("scan*/c")], "the prefix must match literally");
+    assert_eq!(redis.delete_keys(&[key("scan/a"), key("scan/missing")]).await.expect("delete_keys"), 1);
+    assert_eq!(scan(key("scan/")).await, vec![key("scan/b")], "deleted key still listed");
+    assert_eq!(redis.delete_keys(&[]).await.expect("delete_keys of nothing"), 0);
+
+    redis.ping().await.expect("ping");
+}
+
+/// Expiry on a `RedisPort`. `advance` moves time forward by at least the
+/// given duration: advance a shared `ManualClock`, or sleep for a real server.
+pub async fn redis_port_ttl<R, F, Fut>(redis: &R, advance: F)
+where
+    R: RedisPort,
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let ns = namespace("ttl");
+    let key = |name: &str| format!("{}{}", ns, name);
+    let page = StoredPage::new("expiring");
+    let failure = UrlFailure { kind: FailureKind::Temporary, reason: None, failed_at: 1 };
+
+    redis.write_cache_and_clear(&key("cache"), &page, 1, 2).await.expect("write_cache_and_clear");
+    redis.set_inflight_fields(&key("inflight"), None, Some(1), 1).await.expect("set_inflight_fields");
+    redis.set_failure(&key("failed"), &failure, 1).await.expect("set_failure");
+    let ttl = redis.remaining_ttl(&key("cache")).await.expect("remaining_ttl");
+    assert!(ttl.is_some_and(|t| t > Duration::ZERO && t <= Duration::from_secs(2)), "remaining_ttl is {:?}", ttl);
+    assert_eq!(redis.remaining_ttl(&key("missing")).await.expect("remaining_ttl"), None, "missing keys have no ttl");
+
+    // mark_refresh must not extend the cache TTL
+    advance(Duration::from_millis(1_100)).await;
+    redis.mark_refresh(&key("cache"), None, Some(2)).await.expect("mark_refresh");
+    assert!(redis.hgetall(&key("inflight")).await.expect("hgetall").is_empty(), "inflight_ttl not applied");
+    assert_eq!(redis.get_failures(&[key("failed")]).await.expect("get_failures"), vec![None], "failure ttl not applied");
+    assert!(!redis.hgetall(&key("cache")).await.expect("hgetall").is_empty(), "cache entry expired early");
+
+    advance(Duration::from_millis(1_000)).await;
+    assert!(redis.hgetall(&key("cache")).await.expect("hgetall").is_empty(), "cache_ttl not applied");
+}
+
+/// Batch lookups and writes on a `MongoPort` that supports `save_pages`.
+pub async fn mongo_port<M: MongoPort>(mongo: &M) {
+    let ns = namespace("mongo");
+    let url = |name: &str| format!("https://{}.example/{}", ns.replace("::", "-"), name);
+    let empty = mongo.find_by_urls(&[]).await.expect("find_by_urls of nothing");
+    assert!(empty.is_empty(), "an empty lookup finds nothing");
+
+    let mut pages: HashMap<String, StoredPage> = (0..1_500).map(|i| (url(&i.to_string()), StoredPage::new(format!("page {}", i)))).collect();
+    pages.insert(url("full"), sample_page());
+    mongo.save_pages(&pages).await.expect("save_pages");
+
+    // duplicates and unknown URLs in the request
+    let request = vec![url("full"), url("unknown"), url("full"), url("7")];
+    let found = mongo.find_by_urls(&request).await.expect("find_by_urls");
+    assert_eq!(found.len(), 2, "one entry per distinct stored URL, none for unknown ones");
+    assert_eq!(found.get(&url("full")), Some(&sample_page()), "page or metadata changed in storage");
+    assert_eq!(found.get(&url("7")), Some(&StoredPage::new("page 7")));
+
+    // lookups larger than any reasonable single query
+    let all: Vec<String> = pages.keys().cloned().collect();
+    assert_eq!(mongo.find_by_urls(&all).await.expect("find_by_urls").len(), pages.len(), "large lookup lost pages");
+
+    // saving again replaces the page
+    let newer = StoredPage::new("newer").crawled_at(1_800_000_000_000);
+    mongo.save_pages(&HashMap::from([(url("full"), newer.clone())])).await.expect("save_pages");
+    let found = mongo.find_by_urls(&[url("full")]).await.expect("find_by_urls");
+    assert_eq!(found.get(&url("full")), Some(&newer), "save_pages must replace the stored page");
+
+    mongo.ping().await.expect("ping");
+}
+
+/// Delivery on a `CrawlerPort`. `received` returns every URL the crawler
+/// side has seen so far, in any order.
+pub async fn crawler_port<C, F, Fut>(crawler: &C, received: F)
+where
+    C: CrawlerPort,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Vec<String>>,
+{
+    let ns = namespace("crawler");
+    let urls: Vec<String> = (0..5).map(|i| format!("https://{}.example/{}", ns.replace("::", "-"), i)).collect();
+    crawler.send_batch(&urls).await.expect("send_batch");
+    let seen = received().await;
+    for url in &urls {
+        assert!(seen.contains(url), "{} was acknowledged but not delivered", url);
+    }
+}
+
+/// Every call on a `RedisPort` whose backend is unreachable must fail
+/// rather than report empty results.
+pub async fn redis_port_unreachable<R: RedisPort>(redis: &R) {
+    let key = namespace("down");
+    let failure = UrlFailure { kind: FailureKind::Temporary, reason: None, failed_at: 1 };
+    assert!(redis.hgetall(&key).await.is_err(), "hgetall must fail");
+    assert!(redis.multi_hgetall(std::slice::from_ref(&key)).await.is_err(), "multi_hgetall must fail");
+    assert!(redis.write_cache_and_clear(&key, &sample_page(), 1, 60).await.is_err(), "write_cache_and_clear must fail");
+    assert!(redis.set_inflight_fields(&key, Some(1), None, 60).await.is_err(), "set_inflight_fields must fail");
+    assert!(redis.mark_refresh(&key, Some(1), None).await.is_err(), "mark_refresh must fail");
+    assert!(redis.get_failures(std::slice::from_ref(&key)).await.is_err(), "get_failures must fail");
+    assert!(redis.set_failure(&key, &failure, 60).await.is_err(), "set_failure must fail");
+    assert!(redis.clear_failure(&key).await.is_err(), "clear_failure must fail");
+    assert!(redis.remaining_ttl(&key).await.is_err(), "remaining_ttl must fail");
+    assert!(redis.scan_prefix(&key).await.is_err(), "scan_prefix must fail");
+    assert!(redis.scan_match(&key, "*").await.is_err(), "scan_match must fail");
+    assert!(redis.delete_keys(std::slice::from_ref(&key)).await.is_err(), "delete_keys must fail");
+    assert!(redis.ping().await.is_err(), "ping must fail");
+}
+
+/// Lookups on a `MongoPort` whose backend is unreachable must fail rather
+/// than report every URL as missing.
+pub async fn mongo_port_unreachable<M: MongoPort>(mongo: &M) {
+    let url = format!("https://{}.example/", namespace("down").replace("::", "-"));
+    assert!(mongo.find_by_urls(&[url]).await.is_err(), "find_by_urls must fail");
+    assert!(mongo.ping().await.is_err(), "ping must fail");
+}
+
+/// Dispatch to an unreachable crawler must fail so the error reaches the
+/// caller instead of the URLs silently going nowhere.
+pub async fn crawler_port_unreachable<C: CrawlerPort>(crawler: &C) {
+    let url = format!("https://{}.example/", namespace("down").replace("::", "-"));
+    assert!(crawler.send_batch(&[url]).await.is_err(), "send_batch must fail");
+    assert!(crawler.ping().await.is_err(), "ping must fail");
+}
@@ -0,0 +1,47 @@
+use std::io::Read;
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_audit_log_keeps_forwarded_headers_apart_from_the_peer() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_groove-throttle"))
+        .env_clear()
+        .env("RUST_LOG", "warn,audit=info")
+        .env("BIND_ADDR", format!("127.0.0.1:{}", port))
+        .env("REDIS_MODE", "memory")
+        .env("STORAGE", "sqlite")
+        .env("SQLITE_PATH", dir.path().join("pages.sqlite"))
+        .env("CRAWLER_URL", "http://127.0.0.1:9/crawl")
+        .env("ADMIN_TOKEN", "admin-s3cret")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("start server");
+    let base = format!("http://127.0.0.1:{}", port);
+    let started = Instant::now();
+    while reqwest::get(format!("{}/healthz", base)).await.is_err() {
+        assert!(started.elapsed() < Duration::from_secs(10), "server did not come up");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/purge", base))
+        .bearer_auth("admin-s3cret")
+        .header("X-Forwarded-For", "203.0.113.7")
+        .json(&serde_json::json!({ "url": "https://example.com/a" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let mut log = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut log).unwrap();
+    let line = log.lines().find(|l| l.contains("purged")).expect("audit line");
+    assert!(line.contains("admin (127.0.0.1:"), "peer is not the socket address: {}", line);
+    assert!(line.contains("unverified forwarded-for \"203.0.113.7\""), "forwarded value missing: {}", line);
+}
@@ -0,0 +1,116 @@
+use groove_throttle::adapters::chaos::{Chaos, ChaosControl, Fault, FaultPlan};
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::config::Config;
+use groove_throttle::domain::StoredPage;
+use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort, RedisPort};
+use groove_throttle::service::LoadReducerService;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+struct StaticMongo(HashMap<String, StoredPage>);
+
+impl MongoPort for StaticMongo {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        Ok(urls.iter().filter_map(|u| Some((u.clone(), self.0.get(u)?.clone()))).collect())
+    }
+
+    async fn ping(&self) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct NullCrawler;
+
+impl CrawlerPort for NullCrawler {
+    async fn send_batch(&self, _urls: &[String]) -> Result<(), BoxError> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_calls_are_recorded_with_their_arguments() {
+    let stored = HashMap::from([("https://example.com/a".to_string(), StoredPage::new("a"))]);
+    let redis = Chaos::new("redis", MemoryRedisAdapter::new(100));
+    let mongo = Chaos::new("storage", StaticMongo(stored));
+    let crawler = Chaos::new("crawler", NullCrawler);
+    for control in [&redis.control, &mongo.control, &crawler.control] {
+        control.record();
+    }
+    let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), Config::default());
+
+    let urls = vec!["https://example.com/a".to_string(), "https://example.com/b".to_string()];
+    service.process(urls.clone()).await.unwrap();
+
+    let lookups = mongo.control.calls_to("find_by_urls");
+    assert_eq!(lookups.len(), 1);
+    let mut looked_up = lookups[0].args.clone();
+    looked_up.sort();
+    assert_eq!(looked_up, urls);
+    let sends = crawler.control.calls_to("send_batch");
+    assert_eq!(sends.len(), 1);
+    assert_eq!(sends[0].args, vec!["https://example.com/b"]);
+    let writes = redis.control.calls_to("write_cache_and_clear");
+    assert_eq!(writes[0].args[0], "rcs::https://example.com/a");
+
+    redis.control.reset();
+    service.process(urls).await.unwrap();
+    assert!(redis.control.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_scripted_faults_apply_in_order() {
+    let redis = Chaos::new("redis", MemoryRedisAdapter::new(100));
+    redis.control.script(
+        "hgetall",
+        [None, Some(Fault::Error("boom".to_string())), Some(Fault::Delay(Duration::from_millis(20)))],
+    );
+
+    assert!(redis.hgetall("k").await.is_ok());
+    let err = redis.hgetall("k").await.unwrap_err();
+    assert!(err.to_string().contains("boom"), "{}", err);
+    let started = Instant::now();
+    assert!(redis.hgetall("k").await.is_ok());
+    assert!(started.elapsed() >= Duration::from_millis(20));
+    // other methods and later calls are not affected
+    assert!(redis.multi_hgetall(&["k".to_string()]).await.is_ok());
+    assert!(redis.hgetall("k").await.is_ok());
+
+    redis.control.script("ping", [Some(Fault::Timeout(Duration::from_millis(10)))]);
+    assert!(RedisPort::ping(&redis).await.unwrap_err().to_string().contains("timeout"));
+}
+
+#[tokio::test]
+async fn test_random_faults_follow_the_plan() {
+    let control = ChaosControl::new("storage");
+    let mongo = Chaos { inner: StaticMongo(HashMap::new()), control: control.clone() };
+    let invalid = FaultPlan { error_probability: 1.5, ..FaultPlan::default() };
+    assert!(control.set_plan(invalid).is_err());
+
+    let plan = FaultPlan {
+        methods: vec!["find_by_urls".to_string()],
+        error_probability: 0.5,
+        seed: Some(7),
+        ..FaultPlan::default()
+    };
+    control.set_plan(plan.clone()).unwrap();
+    let mut outcomes = Vec::new();
+    for _ in 0..200 {
+        outcomes.push(mongo.find_by_urls(&[]).await.is_err());
+    }
+    let failed = outcomes.iter().filter(|f| **f).count();
+    assert!((60..140).contains(&failed), "{} of 200 calls failed", failed);
+    assert!(MongoPort::ping(&mongo).await.is_ok(), "methods outside the plan are left alone");
+
+    // the same seed gives the same sequence
+    control.set_plan(plan).unwrap();
+    for expected in outcomes {
+        assert_eq!(mongo.find_by_urls(&[]).await.is_err(), expected);
+    }
+
+    // an injected failure reaches the caller of the service
+    let service = LoadReducerService::new(MemoryRedisAdapter::new(10), mongo, NullCrawler, Config::default());
+    control.set_plan(FaultPlan { error_probability: 1.0, ..FaultPlan::default() }).unwrap();
+    assert!(service.process(vec!["https://example.com/".to_string()]).await.is_err());
+}
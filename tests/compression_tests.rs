@@ -0,0 +1,74 @@
+use groove_throttle::adapters::compression::{ENCODING_FIELD, PayloadCompression, decode_hash};
+use groove_throttle::config::{CacheCompression, Config};
+use std::collections::HashMap;
+
+fn html(len: usize) -> String {
+    "<p>groove throttle</p>".repeat(len / 21 + 1)[..len].to_string()
+}
+
+fn stored_hash(bytes: Vec<u8>, encoding: Option<&str>) -> HashMap<String, Vec<u8>> {
+    let mut hash = HashMap::new();
+    hash.insert("data".to_string(), bytes);
+    hash.insert("fetched_at".to_string(), b"1700000000000".to_vec());
+    if let Some(encoding) = encoding {
+        hash.insert(ENCODING_FIELD.to_string(), encoding.as_bytes().to_vec());
+    }
+    hash
+}
+
+#[test]
+fn test_large_payloads_round_trip_through_each_codec() {
+    let data = html(8_192);
+    for codec in [CacheCompression::Zstd, CacheCompression::Gzip] {
+        let compression = PayloadCompression { codec, min_bytes: 1_024 };
+        let (bytes, encoding) = compression.encode(&data).unwrap();
+        assert!(encoding.is_some(), "{:?} should compress", codec);
+        assert!(bytes.len() < data.len() / 4);
+
+        let hash = decode_hash("k", stored_hash(bytes, encoding));
+        assert_eq!(hash["data"], data);
+        assert_eq!(hash["fetched_at"], "1700000000000");
+        assert!(!hash.contains_key(ENCODING_FIELD));
+    }
+    let metrics = groove_throttle::metrics::render();
+    assert!(metrics.contains("groove_cache_compression_ratio_count"));
+}
+
+#[test]
+fn test_small_payloads_and_legacy_entries_stay_plain() {
+    let compression = PayloadCompression { codec: CacheCompression::Zstd, min_bytes: 1_024 };
+    let (bytes, encoding) = compression.encode("short").unwrap();
+    assert_eq!(bytes, b"short");
+    assert_eq!(encoding, None);
+
+    // written before compression existed: no marker field
+    let hash = decode_hash("k", stored_hash(b"legacy".to_vec(), None));
+    assert_eq!(hash["data"], "legacy");
+
+    // a corrupt payload reads as a miss rather than failing the request
+    let hash = decode_hash("k", stored_hash(b"not zstd".to_vec(), Some("zstd")));
+    assert!(!hash.contains_key("data"));
+    assert_eq!(hash["fetched_at"], "1700000000000");
+}
+
+#[test]
+fn test_codec_is_configurable_from_env() {
+    let config = Config::from_sources(None, |name| (name == "CACHE_COMPRESSION").then(|| "Gzip".to_string())).unwrap();
+    assert_eq!(config.cache_compression, CacheCompression::Gzip);
+    assert_eq!(PayloadCompression::from_config(&config).min_bytes, 1_024);
+}
+
+#[test]
+fn test_payloads_that_do_not_shrink_stay_plain_and_out_of_the_ratio() {
+    // too short for the codec headers to pay off
+    let data = "<p>hi</p>";
+    for codec in [CacheCompression::Zstd, CacheCompression::Gzip] {
+        let compression = PayloadCompression { codec, min_bytes: 0 };
+        let (bytes, encoding) = compression.encode(data).unwrap();
+        assert_eq!(encoding, None, "{:?}", codec);
+        assert_eq!(bytes, data.as_bytes());
+    }
+    // only payloads stored compressed are observed, so none is at or below 1
+    let metrics = groove_throttle::metrics::render();
+    assert!(metrics.contains("groove_cache_compression_ratio_bucket{le=\"1\"} 0"), "{}", metrics);
+}
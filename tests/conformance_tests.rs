@@ -0,0 +1,139 @@
+use groove_throttle::adapters::compression::PayloadCompression;
+use groove_throttle::adapters::crawler_adapter::ReqwestCrawlerAdapter;
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::adapters::mongo_adapter::{MongoAdapter, QueryOptions};
+use groove_throttle::adapters::redis_adapter::DeadpoolRedisAdapter;
+use groove_throttle::adapters::sqlite_adapter::SqliteAdapter;
+use groove_throttle::clock::ManualClock;
+use groove_throttle::ports::CrawlerPort;
+use groove_throttle::testkit;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+#[tokio::test]
+async fn test_memory_redis_conforms() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(10_000).with_clock(clock.clone());
+    testkit::redis_port(&redis).await;
+    testkit::redis_port_ttl(&redis, |by| {
+        clock.advance(by);
+        async {}
+    })
+    .await;
+}
+
+/// Runs against a real server when REDIS_TEST_URL is set, e.g. the `redis`
+/// service in docker-compose.yml.
+#[tokio::test]
+async fn test_deadpool_redis_conforms() {
+    let Ok(url) = std::env::var("REDIS_TEST_URL") else {
+        eprintln!("REDIS_TEST_URL not set, skipping");
+        return;
+    };
+    let pool = deadpool_redis::Config::from_url(url).create_pool(Some(deadpool_redis::Runtime::Tokio1)).unwrap();
+    let redis = DeadpoolRedisAdapter { pool, compression: PayloadCompression::disabled() };
+    testkit::redis_port(&redis).await;
+    testkit::redis_port_ttl(&redis, tokio::time::sleep).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_store_conforms() {
+    testkit::mongo_port(&SqliteAdapter::in_memory().unwrap()).await;
+}
+
+/// Runs against a real server when MONGO_TEST_URL is set, e.g. the `mongo`
+/// service in docker-compose.yml.
+#[tokio::test]
+async fn test_mongo_store_conforms() {
+    let Ok(url) = std::env::var("MONGO_TEST_URL") else {
+        eprintln!("MONGO_TEST_URL not set, skipping");
+        return;
+    };
+    let client = mongodb::Client::with_uri_str(url).await.unwrap();
+    let coll = client.database("groove_throttle_test").collection("url_data");
+    testkit::mongo_port(&MongoAdapter { coll, query: QueryOptions::default() }).await;
+}
+
+// Crawler endpoint on a local port: answers every request with 200 and
+// keeps the URLs posted to it.
+async fn local_crawler() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/crawl", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = received.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_crawler_connection(stream, sink.clone()));
+        }
+    });
+    (url, received)
+}
+
+async fn serve_crawler_connection(stream: TcpStream, sink: Arc<Mutex<Vec<String>>>) {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    // one request after the other on a kept-alive connection
+    while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+        let mut content_length = 0;
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                return;
+            }
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        if let Ok(urls) = serde_json::from_slice::<Vec<String>>(&body) {
+            sink.lock().unwrap().extend(urls);
+        }
+        if stream.get_mut().write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.is_err() {
+            return;
+        }
+        line.clear();
+    }
+}
+
+#[tokio::test]
+async fn test_reqwest_crawler_conforms() {
+    let (url, received) = local_crawler().await;
+    let crawler = ReqwestCrawlerAdapter { client: reqwest::Client::new(), url };
+    testkit::crawler_port(&crawler, || async { received.lock().unwrap().clone() }).await;
+    crawler.ping().await.expect("ping");
+}
+
+#[tokio::test]
+async fn test_unreachable_backends_report_errors() {
+    // nothing listens on port 1
+    let mut config = deadpool_redis::Config::from_url("redis://127.0.0.1:1");
+    config.pool = Some(deadpool_redis::PoolConfig {
+        timeouts: deadpool_redis::Timeouts {
+            wait: Some(Duration::from_millis(500)),
+            create: Some(Duration::from_millis(500)),
+            recycle: Some(Duration::from_millis(500)),
+        },
+        ..Default::default()
+    });
+    let pool = config.create_pool(Some(deadpool_redis::Runtime::Tokio1)).unwrap();
+    testkit::redis_port_unreachable(&DeadpoolRedisAdapter { pool, compression: PayloadCompression::disabled() }).await;
+
+    let options = mongodb::options::ClientOptions::parse("mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=500")
+        .await
+        .unwrap();
+    let coll = mongodb::Client::with_options(options).unwrap().database("test").collection("url_data");
+    testkit::mongo_port_unreachable(&MongoAdapter { coll, query: QueryOptions::default() }).await;
+
+    let crawler = ReqwestCrawlerAdapter { client: reqwest::Client::new(), url: "http://127.0.0.1:1/crawl".to_string() };
+    testkit::crawler_port_unreachable(&crawler).await;
+}
@@ -1,3 +1,4 @@
+use groove_throttle::domain::{StoredPage, UrlFailure};
 use groove_throttle::ports::{BoxError, RedisPort};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -41,7 +42,8 @@ impl RedisPort for CoordinatedRedisSimple {
     async fn write_cache_and_clear(
         &self,
         _key: &str,
-        _data: &str,
+        _page: &StoredPage,
+        _fetched_at: u64,
         _cache_ttl: u64,
     ) -> Result<(), BoxError> {
         Ok(())
@@ -54,9 +56,29 @@ impl RedisPort for CoordinatedRedisSimple {
         _inflight_ttl: u64,
     ) -> Result<(), BoxError> {
         eprintln!("[CoordSimple] set_inflight_fields called, notifying");
-        self.notify.notify_waiters();
+        // t1 finishes before t2 waits; notify_one keeps the permit for it,
+        // where notify_waiters would wake nobody and leave t2 hanging
+        self.notify.notify_one();
         Ok(())
     }
+
+    async fn mark_refresh(
+        &self,
+        _key: &str,
+        _last_mongo: Option<u64>,
+        _last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        Ok(())
+    }
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        Ok(vec![None; keys.len()])
+    }
+    async fn set_failure(&self, _key: &str, _failure: &UrlFailure, _ttl: u64) -> Result<(), BoxError> {
+        Ok(())
+    }
+    async fn clear_failure(&self, _key: &str) -> Result<bool, BoxError> {
+        Ok(false)
+    }
 }
 
 #[tokio::test]
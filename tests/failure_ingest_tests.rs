@@ -0,0 +1,62 @@
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+// The server binary, killed when the test is done with it
+struct Server {
+    child: Child,
+    base: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+async fn start_server(dir: &tempfile::TempDir) -> Server {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let child = Command::new(env!("CARGO_BIN_EXE_groove-throttle"))
+        .env_clear()
+        .env("RUST_LOG", "warn")
+        .env("BIND_ADDR", format!("127.0.0.1:{}", port))
+        .env("REDIS_MODE", "memory")
+        .env("STORAGE", "sqlite")
+        .env("SQLITE_PATH", dir.path().join("pages.sqlite"))
+        .env("CRAWLER_URL", "http://127.0.0.1:9/crawl")
+        .env("ADMIN_TOKEN", "admin-s3cret")
+        .env("FAILURE_INGEST_TOKEN", "crawler-s3cret")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("start server");
+    let server = Server { child, base: format!("http://127.0.0.1:{}", port) };
+
+    let started = Instant::now();
+    while reqwest::get(format!("{}/healthz", server.base)).await.is_err() {
+        assert!(started.elapsed() < Duration::from_secs(10), "server did not come up");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    server
+}
+
+#[tokio::test]
+async fn test_failure_reports_need_the_ingest_or_an_admin_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = start_server(&dir).await;
+    let client = reqwest::Client::new();
+    let report = serde_json::json!([{ "url": "https://example.com/a", "kind": "permanent" }]);
+    let post = |token: Option<&str>| {
+        let request = client.post(format!("{}/api/failures", server.base)).json(&report);
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+        .send()
+    };
+
+    assert_eq!(post(None).await.unwrap().status(), 401);
+    assert_eq!(post(Some("guess")).await.unwrap().status(), 401);
+    assert_eq!(post(Some("crawler-s3cret")).await.unwrap().status(), 204);
+    assert_eq!(post(Some("admin-s3cret")).await.unwrap().status(), 204);
+}
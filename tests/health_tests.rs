@@ -0,0 +1,92 @@
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::config::Config;
+use groove_throttle::health::CheckState;
+use groove_throttle::domain::StoredPage;
+use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort};
+use groove_throttle::service::LoadReducerService;
+use std::collections::HashMap;
+
+// Mongo mock without a ping of its own
+#[derive(Clone)]
+struct UncheckedMongo;
+
+impl MongoPort for UncheckedMongo {
+    async fn find_by_urls(&self, _urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        Ok(HashMap::new())
+    }
+}
+
+// Mongo mock whose ping never completes
+#[derive(Clone)]
+struct HangingMongo;
+
+impl MongoPort for HangingMongo {
+    async fn find_by_urls(&self, _urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        Ok(HashMap::new())
+    }
+    async fn ping(&self) -> Result<(), BoxError> {
+        futures::future::pending::<()>().await;
+        Ok(())
+    }
+}
+
+// Crawler mock whose ping always fails
+#[derive(Clone)]
+struct DownCrawler;
+
+impl CrawlerPort for DownCrawler {
+    async fn send_batch(&self, _urls: &[String]) -> Result<(), BoxError> {
+        Ok(())
+    }
+    async fn ping(&self) -> Result<(), BoxError> {
+        Err("connection refused".into())
+    }
+}
+
+#[tokio::test]
+async fn test_readiness_reports_timeout_and_skips_crawler_by_default() {
+    let config = Config {
+        readiness_timeout_ms: 20,
+        readiness_check_crawler: false,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(MemoryRedisAdapter::new(100), HangingMongo, DownCrawler, config);
+
+    let report = service.readiness().await;
+    assert!(!report.ready);
+    assert_eq!(report.dependencies["redis"].status, CheckState::Up);
+    assert_eq!(report.dependencies["mongo"].status, CheckState::Down);
+    assert!(report.dependencies["mongo"].error.as_deref().unwrap().contains("timed out"));
+    assert_eq!(report.dependencies["crawler"].status, CheckState::Skipped);
+}
+
+#[tokio::test]
+async fn test_readiness_checks_crawler_when_enabled() {
+    let config = Config {
+        readiness_timeout_ms: 20,
+        readiness_check_crawler: true,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(MemoryRedisAdapter::new(100), HangingMongo, DownCrawler, config);
+
+    let report = service.readiness().await;
+    assert_eq!(report.dependencies["crawler"].status, CheckState::Down);
+    assert_eq!(
+        report.dependencies["crawler"].error.as_deref(),
+        Some("connection refused")
+    );
+}
+
+#[tokio::test]
+async fn test_readiness_reports_ports_without_a_ping_as_down() {
+    let config = Config {
+        readiness_timeout_ms: 20,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(MemoryRedisAdapter::new(100), UncheckedMongo, DownCrawler, config);
+
+    let report = service.readiness().await;
+    assert!(!report.ready);
+    assert_eq!(report.dependencies["redis"].status, CheckState::Up);
+    assert_eq!(report.dependencies["mongo"].status, CheckState::Down);
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "kafka")]
+
+use groove_throttle::adapters::kafka_crawler_adapter::{KafkaCrawlerAdapter, message_key};
+use groove_throttle::config::{Config, CrawlerMode};
+use groove_throttle::ports::CrawlerPort;
+use rdkafka::ClientConfig;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Message;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_messages_are_keyed_by_host() {
+    assert_eq!(message_key("https://Example.com/a?b=1"), "example.com");
+    assert_eq!(message_key("http://example.com:8080/"), "example.com");
+    assert_eq!(message_key("not a url"), "not a url");
+}
+
+#[test]
+fn test_kafka_mode_from_env() {
+    let lookup = |name: &str| match name {
+        "CRAWLER_MODE" => Some("kafka".to_string()),
+        "KAFKA_BROKERS" => Some("b1:9092,b2:9092".to_string()),
+        "KAFKA_TOPIC" => Some("crawl".to_string()),
+        _ => None,
+    };
+    let config = Config::from_sources(None, lookup).unwrap();
+    assert_eq!(config.crawler_mode, CrawlerMode::Kafka);
+    assert_eq!(config.kafka_brokers, "b1:9092,b2:9092");
+    assert_eq!(config.kafka_topic, "crawl");
+
+    let no_topic = Config::from_sources(None, |name| match name {
+        "CRAWLER_MODE" => Some("kafka".to_string()),
+        "KAFKA_TOPIC" => Some(String::new()),
+        _ => None,
+    });
+    assert!(no_topic.is_err());
+}
+
+/// Needs a broker, e.g. the `redpanda` service in docker-compose.yml; set
+/// KAFKA_TEST_BROKERS to run it.
+#[tokio::test]
+async fn test_batch_is_published_keyed_by_host() {
+    let Ok(brokers) = std::env::var("KAFKA_TEST_BROKERS") else {
+        eprintln!("KAFKA_TEST_BROKERS not set, skipping");
+        return;
+    };
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let topic = format!("groove-test-{}", nanos);
+    let adapter = KafkaCrawlerAdapter::new(&brokers, &topic, Duration::from_secs(10)).unwrap();
+
+    let urls: Vec<String> = ["https://a.example/1", "https://b.example/1", "https://a.example/2"]
+        .iter()
+        .map(|u| u.to_string())
+        .collect();
+    adapter.send_batch(&urls).await.unwrap();
+
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .set("group.id", &topic)
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .unwrap();
+    consumer.subscribe(&[&topic]).unwrap();
+    let mut by_key: HashMap<String, Vec<String>> = HashMap::new();
+    for _ in 0..urls.len() {
+        let msg = tokio::time::timeout(Duration::from_secs(30), consumer.recv()).await.unwrap().unwrap();
+        let key = String::from_utf8(msg.key().unwrap().to_vec()).unwrap();
+        let url = String::from_utf8(msg.payload().unwrap().to_vec()).unwrap();
+        by_key.entry(key).or_default().push(url);
+    }
+    // one partition per key, so a host's URLs arrive in the order sent
+    assert_eq!(by_key["a.example"], vec!["https://a.example/1", "https://a.example/2"]);
+    assert_eq!(by_key["b.example"], vec!["https://b.example/1"]);
+}
@@ -0,0 +1,138 @@
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::clock::ManualClock;
+use groove_throttle::config::Config;
+use groove_throttle::domain::{FailureKind, StoredPage, UrlFailure};
+use groove_throttle::policy::{PolicyEngine, PolicyRule};
+use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort, PolicyStore, RedisPort};
+use groove_throttle::service::LoadReducerService;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_cache_write_replaces_hash_and_expires() {
+    let clock = ManualClock::new(1_000_000);
+    let redis = MemoryRedisAdapter::new(10).with_clock(clock.clone());
+    redis.set_inflight_fields("k", Some(1), Some(2), 60).await.unwrap();
+    redis.write_cache_and_clear("k", &StoredPage::new("body").crawled_at(7), 100, 1).await.unwrap();
+
+    let hash = redis.hgetall("k").await.unwrap();
+    assert_eq!(hash["data"], "body");
+    assert_eq!(hash["fetched_at"], "100");
+    assert_eq!(hash["crawled_at"], "7");
+    assert!(!hash.contains_key("last_mongo_fetch"));
+    assert_eq!(redis.ttl("k"), Some(Duration::from_secs(1)));
+
+    // mark_refresh keeps the cache TTL, like HSET on an existing key
+    redis.mark_refresh("k", None, Some(3)).await.unwrap();
+    assert!(redis.ttl("k").is_some());
+
+    clock.advance(Duration::from_millis(999));
+    assert_eq!(redis.hgetall("k").await.unwrap()["last_crawler_send"], "3");
+    clock.advance(Duration::from_millis(1));
+    assert!(redis.hgetall("k").await.unwrap().is_empty());
+    assert!(redis.is_empty());
+}
+
+#[tokio::test]
+async fn test_least_recently_used_key_is_evicted() {
+    let redis = MemoryRedisAdapter::new(2);
+    redis.write_cache_and_clear("a", &StoredPage::new("a"), 1, 60).await.unwrap();
+    redis.write_cache_and_clear("b", &StoredPage::new("b"), 1, 60).await.unwrap();
+    // reading `a` makes `b` the oldest
+    redis.hgetall("a").await.unwrap();
+    redis.set_inflight_fields("c", None, Some(5), 60).await.unwrap();
+
+    let keys = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    let hashes = redis.multi_hgetall(&keys).await.unwrap();
+    assert_eq!(hashes[0]["data"], "a");
+    assert!(hashes[1].is_empty());
+    assert_eq!(hashes[2]["last_crawler_send"], "5");
+    assert_eq!(redis.len(), 2);
+}
+
+#[tokio::test]
+async fn test_policies_survive_a_full_store() {
+    let redis = MemoryRedisAdapter::new(3);
+    let rule = PolicyRule {
+        id: "news".to_string(),
+        priority: 0,
+        host: Some("news.example".to_string()),
+        path_prefix: None,
+        pattern: None,
+        cache_ttl_sec: Some(300),
+        cache_soft_ttl_sec: None,
+        mongo_prevent_ms: None,
+        crawler_prevent_ms: None,
+    };
+    redis.save_policy("rcs:policies", &rule).await.unwrap();
+    for i in 0..10 {
+        redis.write_cache_and_clear(&format!("rcs::{}", i), &StoredPage::new("x"), 1, 60).await.unwrap();
+    }
+    assert_eq!(redis.len(), 3);
+
+    let engine = PolicyEngine::default();
+    assert_eq!(engine.refresh_from(&redis, "rcs:policies").await.unwrap(), 1);
+    assert_eq!(engine.resolve("https://news.example/", &Config::default()).cache_ttl_sec, 300);
+    assert!(redis.delete_policy("rcs:policies", "news").await.unwrap());
+    assert!(redis.load_policies("rcs:policies").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_failure_records_round_trip() {
+    let redis = MemoryRedisAdapter::new(10);
+    let failure = UrlFailure { kind: FailureKind::Temporary, reason: Some("timeout".to_string()), failed_at: 9 };
+    redis.set_failure("f", &failure, 60).await.unwrap();
+
+    let keys = vec!["f".to_string(), "none".to_string()];
+    assert_eq!(redis.get_failures(&keys).await.unwrap(), vec![Some(failure), None]);
+    assert!(redis.clear_failure("f").await.unwrap());
+    assert!(!redis.clear_failure("f").await.unwrap());
+}
+
+#[derive(Clone)]
+struct EmptyMongo;
+
+impl MongoPort for EmptyMongo {
+    async fn find_by_urls(&self, _urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        Ok(HashMap::new())
+    }
+}
+
+#[derive(Clone, Default)]
+struct CountingCrawler {
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl CrawlerPort for CountingCrawler {
+    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError> {
+        self.sent.lock().unwrap().extend_from_slice(urls);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_share_one_store() {
+    let redis = MemoryRedisAdapter::new(1_000);
+    let crawler = CountingCrawler::default();
+    let service = Arc::new(LoadReducerService::new(redis.clone(), EmptyMongo, crawler.clone(), Config::default()));
+
+    let urls: Vec<String> = (0..50).map(|i| format!("https://example.com/{}", i)).collect();
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let service = service.clone();
+            let urls = urls.clone();
+            tokio::spawn(async move { service.process(urls).await.unwrap() })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // every URL is marked inflight in the shared store
+    let keys: Vec<String> = urls.iter().map(|u| service.cache_key(u)).collect();
+    let hashes = redis.multi_hgetall(&keys).await.unwrap();
+    assert!(hashes.iter().all(|h| h.contains_key("last_crawler_send")));
+    let sent: std::collections::HashSet<String> = crawler.sent.lock().unwrap().iter().cloned().collect();
+    assert_eq!(sent.len(), urls.len());
+}
@@ -0,0 +1,28 @@
+use groove_throttle::adapters::mongo_adapter::QueryOptions;
+use groove_throttle::config::Config;
+use std::time::Duration;
+
+#[test]
+fn test_lookups_are_split_into_distinct_chunks() {
+    let options = QueryOptions { chunk_size: 2, ..QueryOptions::default() };
+    let urls: Vec<String> = ["a", "b", "a", "c", "d", "b", "e"].iter().map(|u| u.to_string()).collect();
+    assert_eq!(options.chunks(&urls), vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+    assert!(options.chunks(&[]).is_empty());
+}
+
+#[test]
+fn test_query_options_follow_config() {
+    let config = Config {
+        mongo_query_chunk_size: 250,
+        mongo_query_concurrency: 8,
+        mongo_max_time_ms: 1_500,
+        ..Config::default()
+    };
+    let options = QueryOptions::from_config(&config);
+    assert_eq!(options.chunk_size, 250);
+    assert_eq!(options.concurrency, 8);
+    assert_eq!(options.max_time, Some(Duration::from_millis(1_500)));
+
+    let unlimited = Config { mongo_max_time_ms: 0, ..Config::default() };
+    assert_eq!(QueryOptions::from_config(&unlimited).max_time, None);
+}
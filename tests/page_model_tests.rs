@@ -0,0 +1,171 @@
+use mongodb::bson::{self, DateTime, doc, oid::ObjectId};
+use groove_throttle::adapters::mongo_adapter::{NewestPages, PageDocument};
+use groove_throttle::domain::{FailureKind, PageMeta, StoredPage, UrlData, UrlFailure};
+use serde_json::json;
+use std::collections::HashMap;
+
+fn to_hash(page: &StoredPage) -> HashMap<String, String> {
+    page.to_cache_fields().into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+fn full_page() -> StoredPage {
+    StoredPage::new("<html></html>").with_meta(PageMeta {
+        status_code: Some(200),
+        content_type: Some("text/html; charset=utf-8".to_string()),
+        final_url: Some("https://example.com/landing".to_string()),
+        crawled_at: Some(1_700_000_000_000),
+        etag: Some("\"abc\"".to_string()),
+    })
+}
+
+#[test]
+fn test_mongo_document_maps_to_page() {
+    let document = doc! {
+        "url": "https://example.com/",
+        "data": "<html></html>",
+        "status_code": 200,
+        "content_type": "text/html; charset=utf-8",
+        "final_url": "https://example.com/landing",
+        "crawled_at": DateTime::from_millis(1_700_000_000_000),
+        "etag": "\"abc\"",
+        "parser_version": 3,
+    };
+    let (url, page) = bson::from_document::<PageDocument>(document).unwrap().into_page();
+    assert_eq!(url, "https://example.com/");
+    assert_eq!(page, full_page());
+
+    // only url and data are required; crawl times may be plain numbers
+    let minimal = doc! { "url": "u", "data": "d", "crawled_at": 5_i64 };
+    let (_, page) = bson::from_document::<PageDocument>(minimal).unwrap().into_page();
+    assert_eq!(page, StoredPage::new("d").crawled_at(5));
+}
+
+#[test]
+fn test_metadata_survives_the_cache_hash() {
+    assert_eq!(StoredPage::from_cache_fields(&to_hash(&full_page())), Some(full_page()));
+}
+
+#[test]
+fn test_full_and_legacy_response_shapes() {
+    let page = full_page();
+    let item = UrlData {
+        url: "https://example.com/".to_string(),
+        data: page.data.clone(),
+        meta: page.meta.clone(),
+        stale: false,
+        failure: None,
+    };
+    let full = serde_json::to_value(&item).unwrap();
+    assert_eq!(full["status_code"], 200);
+    assert_eq!(full["etag"], "\"abc\"");
+    assert!(full.get("stale").is_none());
+
+    let legacy = serde_json::to_value(item.into_legacy().unwrap()).unwrap();
+    assert_eq!(legacy, json!({ "url": "https://example.com/", "data": "<html></html>" }));
+
+    let failed = UrlData {
+        url: "https://example.com/gone".to_string(),
+        data: serde_json::Value::Null,
+        meta: PageMeta::default(),
+        stale: false,
+        failure: Some(UrlFailure { kind: FailureKind::Permanent, reason: None, failed_at: 1 }),
+    };
+    assert!(failed.into_legacy().is_none());
+}
+
+#[test]
+fn test_structured_data_stays_json_end_to_end() {
+    let document = doc! {
+        "url": "https://example.com/product",
+        "data": {
+            "title": "Lamp",
+            "price": 19.5,
+            "stock": 3_i64,
+            "tags": ["home", "light"],
+            "seen": DateTime::from_millis(0),
+        },
+    };
+    let (_, page) = bson::from_document::<PageDocument>(document).unwrap().into_page();
+    let expected = json!({
+        "title": "Lamp",
+        "price": 19.5,
+        "stock": 3,
+        "tags": ["home", "light"],
+        "seen": { "$date": "1970-01-01T00:00:00Z" },
+    });
+    assert_eq!(page.data, expected);
+
+    // through the cache hash and back
+    let hash = to_hash(&page);
+    assert_eq!(hash["data_format"], "json");
+    let cached = StoredPage::from_cache_fields(&hash).unwrap();
+    assert_eq!(cached.data, expected);
+
+    // a string that looks like JSON is still a string
+    let text = StoredPage::new("[1, 2]");
+    let cached = StoredPage::from_cache_fields(&to_hash(&text)).unwrap();
+    assert_eq!(cached.data, json!("[1, 2]"));
+
+    let item = UrlData {
+        url: "https://example.com/product".to_string(),
+        data: expected.clone(),
+        meta: PageMeta::default(),
+        stale: false,
+        failure: None,
+    };
+    let body = serde_json::to_value(&item).unwrap();
+    assert_eq!(body["data"]["tags"][1], "light");
+    let legacy = item.into_legacy().unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&legacy.data).unwrap(), expected);
+}
+
+#[test]
+fn test_newest_duplicate_wins_in_any_order() {
+    let page = |data: &str, crawled_at: Option<i64>, id: ObjectId| {
+        let mut document = doc! { "_id": id, "url": "https://example.com/dup", "data": data };
+        if let Some(at) = crawled_at {
+            document.insert("crawled_at", DateTime::from_millis(at));
+        }
+        bson::from_document::<PageDocument>(document).unwrap()
+    };
+    let older_id = ObjectId::parse_str("650000000000000000000001").unwrap();
+    let newer_id = ObjectId::parse_str("650000000000000000000002").unwrap();
+
+    for reversed in [false, true] {
+        let mut docs = vec![
+            page("uncrawled", None, newer_id),
+            page("newest", Some(2_000), older_id),
+            page("older", Some(1_000), newer_id),
+        ];
+        if reversed {
+            docs.reverse();
+        }
+        let mut newest = NewestPages::default();
+        for d in docs {
+            newest.insert(d);
+        }
+        assert_eq!(newest.into_pages()["https://example.com/dup"].data, "newest");
+    }
+
+    // same crawl time: the later ObjectId wins
+    let mut newest = NewestPages::default();
+    newest.insert(page("second", Some(1_000), newer_id));
+    newest.insert(page("first", Some(1_000), older_id));
+    assert_eq!(newest.into_pages()["https://example.com/dup"].data, "second");
+}
+
+#[test]
+fn test_duplicates_without_crawl_time_or_object_id_resolve_the_same_in_any_order() {
+    let page = |id: bson::Bson| doc! { "_id": id.clone(), "url": "https://example.com/dup", "data": id.to_string() };
+    // string and integer ids, neither with a crawl time
+    for (first, second) in [(page("a".into()), page("b".into())), (page(1.into()), page(2.into()))] {
+        let kept = |docs: [&bson::Document; 2]| {
+            let mut newest = NewestPages::default();
+            for d in docs {
+                newest.insert(bson::from_document::<PageDocument>(d.clone()).unwrap());
+            }
+            newest.into_pages()["https://example.com/dup"].data.clone()
+        };
+        assert_eq!(kept([&first, &second]), kept([&second, &first]));
+    }
+}
@@ -0,0 +1,188 @@
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::clock::{Clock, ManualClock};
+use groove_throttle::config::Config;
+use groove_throttle::policy::{PolicyEngine, PolicyRule};
+use groove_throttle::domain::StoredPage;
+use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort, RedisPort};
+use groove_throttle::service::LoadReducerService;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn rule(id: &str) -> PolicyRule {
+    PolicyRule {
+        id: id.to_string(),
+        priority: 0,
+        host: None,
+        path_prefix: None,
+        pattern: None,
+        cache_ttl_sec: None,
+        cache_soft_ttl_sec: None,
+        mongo_prevent_ms: None,
+        crawler_prevent_ms: None,
+    }
+}
+
+#[test]
+fn test_resolve_matches_host_prefix_and_pattern() {
+    let engine = PolicyEngine::new(vec![
+        PolicyRule {
+            host: Some("*.news.example".to_string()),
+            cache_ttl_sec: Some(300),
+            ..rule("news")
+        },
+        PolicyRule {
+            host: Some("shop.example".to_string()),
+            path_prefix: Some("/archive/".to_string()),
+            cache_ttl_sec: Some(604_800),
+            ..rule("archive")
+        },
+        PolicyRule {
+            pattern: Some(r"\.pdf$".to_string()),
+            crawler_prevent_ms: Some(1),
+            ..rule("pdf")
+        },
+    ]);
+    let config = Config::default();
+
+    let news = engine.resolve("https://www.news.example/today", &config);
+    assert_eq!(news.rule_id.as_deref(), Some("news"));
+    assert_eq!(news.cache_ttl_sec, 300);
+    assert_eq!(news.crawler_prevent_ms, config.crawler_prevent_ms);
+
+    // the apex domain is not covered by a wildcard rule
+    let apex = engine.resolve("https://news.example/today", &config);
+    assert_eq!(apex.rule_id, None);
+
+    let archive = engine.resolve("https://SHOP.example/archive/2020/item", &config);
+    assert_eq!(archive.rule_id.as_deref(), Some("archive"));
+    assert_eq!(archive.cache_ttl_sec, 604_800);
+    let shop_front = engine.resolve("https://shop.example/today", &config);
+    assert_eq!(shop_front.rule_id, None);
+
+    let pdf = engine.resolve("https://docs.example/manual.pdf", &config);
+    assert_eq!(pdf.rule_id.as_deref(), Some("pdf"));
+    assert_eq!(pdf.crawler_prevent_ms, 1);
+    assert_eq!(pdf.cache_ttl_sec, config.cache_ttl_sec);
+}
+
+#[test]
+fn test_highest_priority_wins_and_invalid_rules_are_skipped() {
+    let engine = PolicyEngine::new(vec![
+        PolicyRule {
+            host: Some("a.example".to_string()),
+            cache_ttl_sec: Some(10),
+            ..rule("low")
+        },
+        PolicyRule {
+            priority: 5,
+            pattern: Some("a\\.example".to_string()),
+            cache_ttl_sec: Some(20),
+            ..rule("high")
+        },
+        PolicyRule {
+            priority: 9,
+            pattern: Some("(".to_string()),
+            ..rule("broken")
+        },
+    ]);
+
+    let ids: Vec<String> = engine.rules().into_iter().map(|r| r.id).collect();
+    assert_eq!(ids, vec!["high", "low"]);
+    let policy = engine.resolve("https://a.example/", &Config::default());
+    assert_eq!(policy.rule_id.as_deref(), Some("high"));
+    assert_eq!(policy.cache_ttl_sec, 20);
+}
+
+#[test]
+fn test_saved_rules_are_checked_against_the_global_ttls() {
+    let config = Config {
+        cache_ttl_sec: 3_600,
+        cache_soft_ttl_sec: 600,
+        ..Config::default()
+    };
+    let host = |id: &str| PolicyRule {
+        host: Some("a.example".to_string()),
+        ..rule(id)
+    };
+
+    // a hard TTL at or below the global soft TTL would turn staleness off
+    let short = PolicyRule { cache_ttl_sec: Some(600), ..host("short") };
+    assert!(short.validate().is_ok());
+    assert!(short.validate_with(&config).unwrap_err().contains("cache_soft_ttl_sec (600)"));
+    // so would a soft TTL at or above the global hard TTL
+    let late = PolicyRule { cache_soft_ttl_sec: Some(3_600), ..host("late") };
+    assert!(late.validate_with(&config).is_err());
+
+    assert!(PolicyRule { cache_ttl_sec: Some(601), ..host("ok") }.validate_with(&config).is_ok());
+    assert!(PolicyRule { cache_soft_ttl_sec: Some(60), ..host("ok") }.validate_with(&config).is_ok());
+    // turning the soft TTL off is always allowed
+    let off = PolicyRule { cache_ttl_sec: Some(60), cache_soft_ttl_sec: Some(0), ..host("off") };
+    assert!(off.validate_with(&config).is_ok());
+}
+
+// Mock Mongo that has a document for every `/stored` URL
+#[derive(Clone)]
+struct MockMongo;
+
+impl MongoPort for MockMongo {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
+        Ok(urls
+            .iter()
+            .filter(|u| u.contains("/stored"))
+            .map(|u| (u.clone(), StoredPage::new("stored")))
+            .collect())
+    }
+}
+
+#[derive(Clone, Default)]
+struct MockCrawler {
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl CrawlerPort for MockCrawler {
+    async fn send_batch(&self, urls: &[String]) -> Result<(), BoxError> {
+        self.sent.lock().unwrap().extend(urls.iter().cloned());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_process_applies_policy_ttl_and_crawler_window() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let crawler = MockCrawler::default();
+    let policies = PolicyEngine::new(vec![PolicyRule {
+        host: Some("news.example".to_string()),
+        cache_ttl_sec: Some(300),
+        crawler_prevent_ms: Some(1_000),
+        ..rule("news")
+    }]);
+
+    // Both missing URLs were sent to the crawler five seconds ago
+    let five_seconds_ago = clock.now_ms() - 5_000;
+    for url in ["https://news.example/missing", "https://other.example/missing"] {
+        let key = format!("rcs::{}", url);
+        redis.set_inflight_fields(&key, Some(five_seconds_ago), Some(five_seconds_ago), 60).await.unwrap();
+    }
+
+    let service = LoadReducerService::new(redis.clone(), MockMongo, crawler.clone(), Config::default())
+        .with_policies(policies)
+        .with_clock(clock.clone());
+    service
+        .process(vec![
+            "https://news.example/stored".to_string(),
+            "https://other.example/stored".to_string(),
+            "https://news.example/missing".to_string(),
+            "https://other.example/missing".to_string(),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(redis.ttl("rcs::https://news.example/stored"), Some(Duration::from_secs(300)));
+    assert_eq!(redis.ttl("rcs::https://other.example/stored"), Some(Duration::from_secs(3600)));
+
+    // only the news URL is outside its (shorter) crawler window
+    let sent = crawler.sent.lock().unwrap();
+    assert_eq!(*sent, vec!["https://news.example/missing".to_string()]);
+}
@@ -0,0 +1,39 @@
+use groove_throttle::adapters::redis_cluster_adapter::group_by_slot;
+use groove_throttle::config::{Config, RedisMode};
+
+#[test]
+fn test_keys_are_grouped_by_slot_in_first_seen_order() {
+    let keys: Vec<String> = [
+        "rcs::{https://a.example/}",
+        "rcs::failed::{https://b.example/}",
+        "rcs::failed::{https://a.example/}",
+        "rcs::{https://b.example/}",
+        "rcs::{https://a.example/}",
+    ]
+    .iter()
+    .map(|k| k.to_string())
+    .collect();
+
+    // keys sharing a hash tag share a slot
+    assert_eq!(group_by_slot(&keys), vec![vec![0, 2, 4], vec![1, 3]]);
+    assert!(group_by_slot(&[]).is_empty());
+}
+
+#[test]
+fn test_cluster_mode_from_env() {
+    let lookup = |name: &str| match name {
+        "REDIS_MODE" => Some("cluster".to_string()),
+        "REDIS_CLUSTER_URLS" => Some("redis://n1:7000, redis://:pw@n2:7001".to_string()),
+        _ => None,
+    };
+    let config = Config::from_sources(None, lookup).unwrap();
+    assert_eq!(config.redis_mode, RedisMode::Cluster);
+    assert_eq!(config.cluster_urls(), vec!["redis://n1:7000", "redis://:pw@n2:7001"]);
+    assert_eq!(config.redacted().redis_cluster_urls[1], "redis://:***@n2:7001");
+
+    // without a node list the single URL seeds the cluster
+    assert_eq!(Config::default().cluster_urls(), vec!["redis://127.0.0.1:6379"]);
+
+    let bad = Config::from_sources(None, |name| (name == "REDIS_CLUSTER_URLS").then(|| "n1:7000".to_string()));
+    assert!(bad.is_err());
+}
@@ -0,0 +1,37 @@
+use deadpool_redis::redis::{ErrorKind, RedisError};
+use groove_throttle::adapters::redis_sentinel_adapter::is_failover_error;
+use groove_throttle::config::{Config, RedisMode};
+use groove_throttle::ports::BoxError;
+
+#[test]
+fn test_sentinel_mode_from_env() {
+    let lookup = |name: &str| match name {
+        "REDIS_MODE" => Some("sentinel".to_string()),
+        "REDIS_SENTINEL_URLS" => Some("redis://s1:26379,redis://:pw@s2:26379".to_string()),
+        "REDIS_SENTINEL_MASTER" => Some("cache".to_string()),
+        "REDIS_SENTINEL_REPLICA_READS" => Some("true".to_string()),
+        _ => None,
+    };
+    let config = Config::from_sources(None, lookup).unwrap();
+    assert_eq!(config.redis_mode, RedisMode::Sentinel);
+    assert_eq!(config.redis_sentinel_urls, vec!["redis://s1:26379", "redis://:pw@s2:26379"]);
+    assert_eq!(config.redis_sentinel_master, "cache");
+    assert!(config.redis_sentinel_replica_reads);
+    assert_eq!(config.redacted().redis_sentinel_urls[1], "redis://:***@s2:26379");
+
+    // sentinel mode cannot fall back to redis_url, which names a data node
+    let bad = Config::from_sources(None, |name| (name == "REDIS_MODE").then(|| "sentinel".to_string()));
+    assert!(bad.is_err());
+}
+
+#[test]
+fn test_failover_errors_trigger_reconnect() {
+    let readonly: BoxError = Box::new(RedisError::from((ErrorKind::ReadOnly, "READONLY")));
+    let dropped: BoxError = Box::new(RedisError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe)));
+    let wrong_type: BoxError = Box::new(RedisError::from((ErrorKind::TypeError, "WRONGTYPE")));
+    let other: BoxError = "not a redis error".into();
+    assert!(is_failover_error(&readonly));
+    assert!(is_failover_error(&dropped));
+    assert!(!is_failover_error(&wrong_type));
+    assert!(!is_failover_error(&other));
+}
@@ -1,4 +1,6 @@
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
 use groove_throttle::config::Config;
+use groove_throttle::domain::{FailureKind, StoredPage, UrlFailure};
 use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort, RedisPort};
 use groove_throttle::service::LoadReducerService;
 use std::collections::HashMap;
@@ -48,14 +50,13 @@ impl RedisPort for CoordinatedRedis {
             // Check if inflight fields already present to avoid missed-notify hang
             {
                 let store = self.store.lock().unwrap();
-                if let Some(entry) = store.get(key) {
-                    if entry.contains_key("last_crawler_send")
-                        || entry.contains_key("last_mongo_fetch")
-                    {
-                        eprintln!("[CoordinatedRedis] inflight already present, not waiting");
-                        let cloned = entry.clone();
-                        return Ok(cloned);
-                    }
+                if let Some(entry) = store.get(key)
+                    && (entry.contains_key("last_crawler_send")
+                        || entry.contains_key("last_mongo_fetch"))
+                {
+                    eprintln!("[CoordinatedRedis] inflight already present, not waiting");
+                    let cloned = entry.clone();
+                    return Ok(cloned);
                 }
             }
             eprintln!("[CoordinatedRedis] second caller waiting for notify...");
@@ -70,13 +71,17 @@ impl RedisPort for CoordinatedRedis {
     async fn write_cache_and_clear(
         &self,
         key: &str,
-        data: &str,
+        page: &StoredPage,
+        fetched_at: u64,
         _cache_ttl: u64,
     ) -> Result<(), BoxError> {
         eprintln!("[CoordinatedRedis] write_cache_and_clear key={}", key);
         let mut store = self.store.lock().unwrap();
         let entry = store.entry(key.to_string()).or_default();
-        entry.insert("data".to_string(), data.to_string());
+        for (field, value) in page.to_cache_fields() {
+            entry.insert(field.to_string(), value);
+        }
+        entry.insert("fetched_at".to_string(), fetched_at.to_string());
         entry.remove("last_mongo_fetch");
         entry.remove("last_crawler_send");
         Ok(())
@@ -106,6 +111,35 @@ impl RedisPort for CoordinatedRedis {
         self.notify.notify_waiters();
         Ok(())
     }
+
+    async fn mark_refresh(
+        &self,
+        key: &str,
+        last_mongo: Option<u64>,
+        last_crawler: Option<u64>,
+    ) -> Result<(), BoxError> {
+        let mut store = self.store.lock().unwrap();
+        let entry = store.entry(key.to_string()).or_default();
+        if let Some(m) = last_mongo {
+            entry.insert("last_mongo_fetch".to_string(), m.to_string());
+        }
+        if let Some(c) = last_crawler {
+            entry.insert("last_crawler_send".to_string(), c.to_string());
+        }
+        Ok(())
+    }
+
+    async fn get_failures(&self, keys: &[String]) -> Result<Vec<Option<UrlFailure>>, BoxError> {
+        Ok(vec![None; keys.len()])
+    }
+
+    async fn set_failure(&self, _key: &str, _failure: &UrlFailure, _ttl: u64) -> Result<(), BoxError> {
+        Ok(())
+    }
+
+    async fn clear_failure(&self, _key: &str) -> Result<bool, BoxError> {
+        Ok(false)
+    }
 }
 
 // Mock Mongo returns empty (missing)
@@ -113,7 +147,7 @@ impl RedisPort for CoordinatedRedis {
 struct MockMongo;
 
 impl MongoPort for MockMongo {
-    async fn find_by_urls(&self, _urls: &[String]) -> Result<HashMap<String, String>, BoxError> {
+    async fn find_by_urls(&self, _urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
         Ok(HashMap::new())
     }
 }
@@ -149,8 +183,12 @@ async fn concurrency_inflight_suppression_single_crawler_call() {
     let mongo = MockMongo;
     let crawler = MockCrawler::new();
 
-    let mut config = Config::from_env();
-    config.crawler_prevent_ms = 0; // allow immediate sends
+    let config = Config {
+        // windows are inclusive, so a zero window would dispatch both calls;
+        // the first has no marker yet and still sends immediately
+        crawler_prevent_ms: 60_000,
+        ..Config::default()
+    };
 
     let service = Arc::new(LoadReducerService::new(
         redis.clone(),
@@ -184,3 +222,29 @@ async fn concurrency_inflight_suppression_single_crawler_call() {
         *sent
     );
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrency_recorded_failure_is_never_dispatched() {
+    let redis = MemoryRedisAdapter::new(100);
+    let crawler = MockCrawler::new();
+    let config = Config {
+        crawler_prevent_ms: 0, // nothing but the failure holds the URL back
+        ..Config::default()
+    };
+    let service = Arc::new(LoadReducerService::new(redis, MockMongo, crawler.clone(), config));
+    let url = "https://example.com/gone".to_string();
+    service.record_failure(&url, FailureKind::Permanent, None).await.unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let service = service.clone();
+            let url = url.clone();
+            tokio::spawn(async move { service.process(vec![url]).await.unwrap() })
+        })
+        .collect();
+    for handle in handles {
+        let res = handle.await.unwrap();
+        assert!(res[0].failure.is_some(), "every caller is told about the failure");
+    }
+    assert_eq!(*crawler.sent_count.lock().unwrap(), 0);
+}
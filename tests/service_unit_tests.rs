@@ -1,79 +1,20 @@
+use groove_throttle::adapters::memory_redis_adapter::MemoryRedisAdapter;
+use groove_throttle::clock::ManualClock;
 use groove_throttle::config::Config;
+use groove_throttle::inspect::Decision;
+use groove_throttle::adapters::chaos::Chaos;
+use groove_throttle::clock::Clock;
+use groove_throttle::domain::{DuplicateUrls, FailureKind, PurgeScope, StoredPage};
 use groove_throttle::ports::{BoxError, CrawlerPort, MongoPort, RedisPort};
-use groove_throttle::service::LoadReducerService;
+use groove_throttle::service::{LoadReducerService, SingleUrl};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-
-// Mock Redis adapter
-#[derive(Clone)]
-struct MockRedis {
-    // key -> hash map
-    store: Arc<Mutex<HashMap<String, HashMap<String, String>>>>,
-}
-
-impl MockRedis {
-    fn new() -> Self {
-        Self {
-            store: Arc::new(Mutex::new(HashMap::new())),
-        }
-    }
-}
-
-impl RedisPort for MockRedis {
-    async fn multi_hgetall(
-        &self,
-        keys: &[String],
-    ) -> Result<Vec<HashMap<String, String>>, BoxError> {
-        let store = self.store.lock().unwrap();
-        let mut res = Vec::new();
-        for k in keys {
-            res.push(store.get(k).cloned().unwrap_or_default());
-        }
-        Ok(res)
-    }
-
-    async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>, BoxError> {
-        let store = self.store.lock().unwrap();
-        Ok(store.get(key).cloned().unwrap_or_default())
-    }
-
-    async fn write_cache_and_clear(
-        &self,
-        key: &str,
-        data: &str,
-        _cache_ttl: u64,
-    ) -> Result<(), BoxError> {
-        let mut store = self.store.lock().unwrap();
-        let entry = store.entry(key.to_string()).or_default();
-        entry.insert("data".to_string(), data.to_string());
-        entry.remove("last_mongo_fetch");
-        entry.remove("last_crawler_send");
-        Ok(())
-    }
-
-    async fn set_inflight_fields(
-        &self,
-        key: &str,
-        last_mongo: Option<u64>,
-        last_crawler: Option<u64>,
-        _inflight_ttl: u64,
-    ) -> Result<(), BoxError> {
-        let mut store = self.store.lock().unwrap();
-        let entry = store.entry(key.to_string()).or_default();
-        if let Some(m) = last_mongo {
-            entry.insert("last_mongo_fetch".to_string(), m.to_string());
-        }
-        if let Some(c) = last_crawler {
-            entry.insert("last_crawler_send".to_string(), c.to_string());
-        }
-        Ok(())
-    }
-}
+use std::time::Duration;
 
 // Mock Mongo adapter
 #[derive(Clone)]
 struct MockMongo {
-    data: Arc<Mutex<HashMap<String, String>>>,
+    data: Arc<Mutex<HashMap<String, StoredPage>>>,
 }
 
 impl MockMongo {
@@ -85,7 +26,7 @@ impl MockMongo {
 }
 
 impl MongoPort for MockMongo {
-    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, String>, BoxError> {
+    async fn find_by_urls(&self, urls: &[String]) -> Result<HashMap<String, StoredPage>, BoxError> {
         let data = self.data.lock().unwrap();
         let mut res = HashMap::new();
         for u in urls {
@@ -121,19 +62,17 @@ impl CrawlerPort for MockCrawler {
 
 #[tokio::test]
 async fn test_cache_hit_no_mongo_no_crawler() {
-    let redis = MockRedis::new();
+    let redis = MemoryRedisAdapter::new(100);
     let mongo = MockMongo::new();
     let crawler = MockCrawler::new();
 
     // Pre-populate redis with data
-    {
-        let mut store = redis.store.lock().unwrap();
-        let mut hash = HashMap::new();
-        hash.insert("data".to_string(), "cached-value".to_string());
-        store.insert("rcs::https://example.com/a".to_string(), hash);
-    }
+    redis
+        .write_cache_and_clear("rcs::https://example.com/a", &StoredPage::new("cached-value"), 0, 600)
+        .await
+        .unwrap();
 
-    let config = Config::from_env();
+    let config = Config::default();
     let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config);
 
     let res = service
@@ -152,7 +91,7 @@ async fn test_cache_hit_no_mongo_no_crawler() {
 
 #[tokio::test]
 async fn test_mongo_fetch_and_cache_write() {
-    let redis = MockRedis::new();
+    let redis = MemoryRedisAdapter::new(100);
     let mongo = MockMongo::new();
     let crawler = MockCrawler::new();
 
@@ -161,11 +100,11 @@ async fn test_mongo_fetch_and_cache_write() {
         let mut data = mongo.data.lock().unwrap();
         data.insert(
             "https://example.com/b".to_string(),
-            "mongo-value".to_string(),
+            StoredPage::new("mongo-value"),
         );
     }
 
-    let config = Config::from_env();
+    let config = Config::default();
     let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config);
 
     let res = service
@@ -176,18 +115,17 @@ async fn test_mongo_fetch_and_cache_write() {
     assert_eq!(res[0].data, "mongo-value");
 
     // Redis should now have cached value
-    let store = redis.store.lock().unwrap();
-    let hash = store.get("rcs::https://example.com/b").unwrap();
+    let hash = redis.hgetall("rcs::https://example.com/b").await.unwrap();
     assert_eq!(hash.get("data").unwrap(), "mongo-value");
 }
 
 #[tokio::test]
 async fn test_missing_triggers_crawler_and_inflight_update() {
-    let redis = MockRedis::new();
+    let redis = MemoryRedisAdapter::new(100);
     let mongo = MockMongo::new();
     let crawler = MockCrawler::new();
 
-    let config = Config::from_env();
+    let config = Config::default();
     let mut cfg = config.clone();
     // make crawler prevent small so it sends immediately in test
     cfg.crawler_prevent_ms = 0;
@@ -199,12 +137,572 @@ async fn test_missing_triggers_crawler_and_inflight_update() {
     assert_eq!(res.len(), 0); // no data
 
     // crawler should have been called
-    let sent = crawler.sent.lock().unwrap();
-    assert_eq!(sent.len(), 1);
-    assert_eq!(sent[0][0], url.clone());
+    assert_eq!(*crawler.sent.lock().unwrap(), vec![vec![url.clone()]]);
 
     // redis should have inflight fields
-    let store = redis.store.lock().unwrap();
-    let hash = store.get(&format!("rcs::{}", url)).unwrap();
+    let hash = redis.hgetall(&format!("rcs::{}", url)).await.unwrap();
     assert!(hash.contains_key("last_crawler_send") || hash.contains_key("last_mongo_fetch"));
 }
+
+#[tokio::test]
+async fn test_stale_entry_served_and_refreshed_in_background() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let url = "https://example.com/stale".to_string();
+    let key = format!("rcs::{}", url);
+
+    // Cached two minutes ago, Mongo has a newer version
+    let cached_at = clock.now_ms();
+    redis.write_cache_and_clear(&key, &StoredPage::new("old-value"), cached_at, 3600).await.unwrap();
+    mongo.data.lock().unwrap().insert(url.clone(), StoredPage::new("new-value"));
+    clock.advance(Duration::from_secs(120));
+
+    let config = Config {
+        cache_soft_ttl_sec: 60,
+        ..Config::default()
+    };
+    let service =
+        LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config).with_clock(clock.clone());
+
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].data, "old-value");
+    assert!(res[0].stale);
+
+    // let the spawned refresh run
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    let hash = redis.hgetall(&key).await.unwrap();
+    assert_eq!(hash.get("data").unwrap(), "new-value");
+    assert_ne!(hash.get("fetched_at").unwrap(), &cached_at.to_string());
+    assert!(!hash.contains_key("last_mongo_fetch"));
+
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(res[0].data, "new-value");
+    assert!(!res[0].stale);
+    assert!(crawler.sent.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_stale_refresh_is_throttled_by_mongo_window() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let url = "https://example.com/stale-claimed".to_string();
+    let key = format!("rcs::{}", url);
+
+    // Stale, but another request started a refresh a second ago
+    redis.write_cache_and_clear(&key, &StoredPage::new("old-value"), clock.now_ms(), 3600).await.unwrap();
+    clock.advance(Duration::from_secs(119));
+    redis.mark_refresh(&key, Some(clock.now_ms()), None).await.unwrap();
+    clock.advance(Duration::from_secs(1));
+    mongo.data.lock().unwrap().insert(url.clone(), StoredPage::new("new-value"));
+
+    let config = Config {
+        cache_soft_ttl_sec: 60,
+        ..Config::default()
+    };
+    let service =
+        LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config).with_clock(clock.clone());
+
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert!(res[0].stale);
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(redis.hgetall(&key).await.unwrap().get("data").unwrap(), "old-value");
+}
+
+#[tokio::test]
+async fn test_outdated_mongo_page_is_served_stale_and_recrawled() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let fresh = "https://example.com/fresh".to_string();
+    let old = "https://example.com/old".to_string();
+    let recently_sent = "https://example.com/old-recently-sent".to_string();
+
+    let now = clock.now_ms();
+    {
+        let mut data = mongo.data.lock().unwrap();
+        data.insert(fresh.clone(), StoredPage::new("fresh-value").crawled_at(now - 10_000));
+        data.insert(old.clone(), StoredPage::new("old-value").crawled_at(now - 7_200_000));
+        data.insert(recently_sent.clone(), StoredPage::new("old-value").crawled_at(now - 7_200_000));
+    }
+    // the crawler already has this one, so it must not be queued again
+    redis.set_inflight_fields(&format!("rcs::{}", recently_sent), None, Some(now - 60_000), 600).await.unwrap();
+
+    let config = Config {
+        mongo_max_age_sec: 3600,
+        ..Config::default()
+    };
+    let service =
+        LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config).with_clock(clock.clone());
+
+    let res = service
+        .process(vec![fresh.clone(), old.clone(), recently_sent.clone()])
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 3);
+    assert!(!res[0].stale);
+    assert_eq!(res[1].data, "old-value");
+    assert!(res[1].stale);
+    assert!(res[2].stale);
+
+    assert_eq!(*crawler.sent.lock().unwrap(), vec![vec![old.clone()]]);
+    let old_hash = redis.hgetall(&format!("rcs::{}", old)).await.unwrap();
+    assert_eq!(old_hash.get("crawled_at").unwrap(), &(now - 7_200_000).to_string());
+    assert_eq!(old_hash.get("last_crawler_send").unwrap(), old_hash.get("fetched_at").unwrap());
+    let recent_hash = redis.hgetall(&format!("rcs::{}", recently_sent)).await.unwrap();
+    assert_eq!(recent_hash.get("last_crawler_send").unwrap(), &(now - 60_000).to_string());
+}
+
+#[tokio::test]
+async fn test_failed_url_is_reported_and_not_dispatched_until_cleared() {
+    let redis = MemoryRedisAdapter::new(100);
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let url = "https://example.com/gone".to_string();
+
+    let config = Config {
+        crawler_prevent_ms: 0,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), config);
+    service
+        .record_failure(&url, FailureKind::Permanent, Some("404".to_string()))
+        .await
+        .unwrap();
+
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(res.len(), 1);
+    assert!(res[0].data.is_null());
+    let failure = res[0].failure.as_ref().unwrap();
+    assert_eq!(failure.kind, FailureKind::Permanent);
+    assert_eq!(failure.reason.as_deref(), Some("404"));
+    assert!(crawler.sent.lock().unwrap().is_empty());
+
+    assert!(service.clear_failure(&url).await.unwrap());
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert!(res.is_empty());
+    assert_eq!(*crawler.sent.lock().unwrap(), vec![vec![url.clone()]]);
+}
+
+#[tokio::test]
+async fn test_hash_tags_wrap_the_url_in_every_key() {
+    let config = Config {
+        redis_hash_tags: true,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(MemoryRedisAdapter::new(100), MockMongo::new(), MockCrawler::new(), config);
+    assert_eq!(service.cache_key("https://example.com/a"), "rcs::{https://example.com/a}");
+    assert_eq!(service.failure_key("https://example.com/a"), "rcs::failed::{https://example.com/a}");
+
+    let plain = LoadReducerService::new(MemoryRedisAdapter::new(100), MockMongo::new(), MockCrawler::new(), Config::default());
+    assert_eq!(plain.cache_key("https://example.com/a"), "rcs::https://example.com/a");
+}
+
+#[tokio::test]
+async fn test_throttle_windows_and_inflight_expiry_follow_the_clock() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let cfg = Config {
+        mongo_prevent_ms: 1_000,
+        crawler_prevent_ms: 10_000,
+        inflight_ttl_sec: 60,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/windows".to_string();
+    let sends = || crawler.sent.lock().unwrap().len();
+
+    service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(sends(), 1);
+
+    // the crawler window holds until its last millisecond and closes at its end
+    clock.advance(Duration::from_millis(9_999));
+    service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(sends(), 1);
+
+    clock.advance(Duration::from_millis(1));
+    service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(sends(), 2);
+
+    // once the inflight markers expire the next request starts over
+    clock.advance(Duration::from_secs(60));
+    assert!(redis.hgetall(&service.cache_key(&url)).await.unwrap().is_empty());
+
+    // a page showing up in Mongo is found on the next lookup past the Mongo window
+    mongo.data.lock().unwrap().insert(url.clone(), StoredPage::new("crawled"));
+    let res = service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(res[0].data, "crawled");
+    assert_eq!(sends(), 2);
+}
+
+#[tokio::test]
+async fn test_zero_crawler_window_dispatches_every_request() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let crawler = MockCrawler::new();
+    let cfg = Config {
+        mongo_prevent_ms: 0,
+        crawler_prevent_ms: 0,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis, MockMongo::new(), crawler.clone(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/unthrottled".to_string();
+
+    // even within the same millisecond
+    service.process(vec![url.clone()]).await.unwrap();
+    service.process(vec![url.clone()]).await.unwrap();
+    clock.advance(Duration::from_millis(1));
+    service.process(vec![url.clone()]).await.unwrap();
+    assert_eq!(crawler.sent.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_duplicate_urls_are_looked_up_and_dispatched_once() {
+    let cached = "https://example.com/cached".to_string();
+    let stored = "https://example.com/stored".to_string();
+    let missing = "https://example.com/missing".to_string();
+    let request = vec![
+        cached.clone(),
+        stored.clone(),
+        missing.clone(),
+        stored.clone(),
+        cached.clone(),
+        missing.clone(),
+    ];
+
+    for (duplicates, expected) in [
+        (DuplicateUrls::Echo, vec![&cached, &stored, &stored, &cached]),
+        (DuplicateUrls::Dedupe, vec![&cached, &stored]),
+    ] {
+        let redis = Chaos::new("redis", MemoryRedisAdapter::new(100));
+        let mongo = Chaos::new("storage", MockMongo::new());
+        let crawler = MockCrawler::new();
+        redis
+            .inner
+            .write_cache_and_clear(&format!("rcs::{}", cached), &StoredPage::new("from cache"), 0, 600)
+            .await
+            .unwrap();
+        mongo.inner.data.lock().unwrap().insert(stored.clone(), StoredPage::new("from mongo"));
+        redis.control.record();
+        mongo.control.record();
+        let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), Config::default());
+
+        let res = service.process_with(request.clone(), duplicates).await.unwrap();
+        let urls: Vec<&String> = res.iter().map(|r| &r.url).collect();
+        assert_eq!(urls, expected, "{:?}", duplicates);
+        assert!(res.iter().all(|r| r.data == if r.url == cached { "from cache" } else { "from mongo" }));
+
+        // the Redis read is the only call that sees every distinct URL
+        let reads = redis.control.calls_to("multi_hgetall");
+        assert_eq!(reads.len(), 1);
+        assert_eq!(reads[0].args.len(), 3, "{:?}", reads[0].args);
+        let lookups = mongo.control.calls_to("find_by_urls");
+        assert_eq!(lookups.len(), 1);
+        let mut looked_up = lookups[0].args.clone();
+        looked_up.sort();
+        assert_eq!(looked_up, vec![missing.clone(), stored.clone()]);
+        assert_eq!(redis.control.calls_to("write_cache_and_clear").len(), 1);
+        assert_eq!(redis.control.calls_to("set_inflight_fields").len(), 1);
+        assert_eq!(*crawler.sent.lock().unwrap(), vec![vec![missing.clone()]]);
+    }
+}
+
+#[tokio::test]
+async fn test_duplicate_urls_default_comes_from_config() {
+    let url = "https://example.com/twice".to_string();
+    let redis = MemoryRedisAdapter::new(100);
+    let mongo = MockMongo::new();
+    mongo.data.lock().unwrap().insert(url.clone(), StoredPage::new("page"));
+
+    let echo = LoadReducerService::new(redis.clone(), mongo.clone(), MockCrawler::new(), Config::default());
+    assert_eq!(echo.process(vec![url.clone(), url.clone()]).await.unwrap().len(), 2);
+
+    let config = Config {
+        duplicate_urls: DuplicateUrls::Dedupe,
+        ..Config::default()
+    };
+    let dedupe = LoadReducerService::new(redis, mongo, MockCrawler::new(), config);
+    assert_eq!(dedupe.process(vec![url.clone(), url.clone()]).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_single_url_reports_data_with_its_ttl_failures_and_queued_urls() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let cfg = Config {
+        cache_ttl_sec: 600,
+        mongo_prevent_ms: 2_500,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis, mongo.clone(), crawler.clone(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/single";
+
+    match service.process_one(url).await.unwrap() {
+        SingleUrl::Queued { retry_after } => assert_eq!(retry_after, Duration::from_millis(2_500)),
+        other => panic!("expected a queued URL, got {:?}", other),
+    }
+    assert_eq!(*crawler.sent.lock().unwrap(), vec![vec![url.to_string()]]);
+
+    // the entry's TTL counts down from the cache write
+    mongo.data.lock().unwrap().insert(url.to_string(), StoredPage::new("found"));
+    clock.advance(Duration::from_secs(3));
+    service.process_one(url).await.unwrap();
+    clock.advance(Duration::from_secs(100));
+    match service.process_one(url).await.unwrap() {
+        SingleUrl::Found { data, ttl } => {
+            assert_eq!(data.data, "found");
+            assert_eq!(ttl, Some(Duration::from_secs(500)));
+        }
+        other => panic!("expected data, got {:?}", other),
+    }
+
+    let failed = "https://example.com/single-failed";
+    service.record_failure(failed, FailureKind::Temporary, None).await.unwrap();
+    match service.process_one(failed).await.unwrap() {
+        SingleUrl::Failed(data) => assert_eq!(data.failure.unwrap().kind, FailureKind::Temporary),
+        other => panic!("expected a failure, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_single_url_ttl_ends_with_the_soft_ttl() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let cfg = Config {
+        cache_ttl_sec: 600,
+        cache_soft_ttl_sec: 120,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis, mongo.clone(), MockCrawler::new(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/soft";
+    mongo.data.lock().unwrap().insert(url.to_string(), StoredPage::new("found"));
+    service.process_one(url).await.unwrap();
+
+    // fresh for what is left of the soft TTL, not the hard one
+    clock.advance(Duration::from_secs(100));
+    match service.process_one(url).await.unwrap() {
+        SingleUrl::Found { ttl, .. } => assert_eq!(ttl, Some(Duration::from_secs(20))),
+        other => panic!("expected data, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_purge_by_url_host_and_prefix_keeps_failure_records() {
+    for hash_tags in [false, true] {
+        let redis = MemoryRedisAdapter::new(100);
+        let cfg = Config {
+            redis_hash_tags: hash_tags,
+            ..Config::default()
+        };
+        let service = LoadReducerService::new(redis.clone(), MockMongo::new(), MockCrawler::new(), cfg);
+        let urls = [
+            "https://example.com/a",
+            "https://example.com/b/1",
+            "http://EXAMPLE.com:8080/c",
+            "https://example.com.other.org/d",
+            "https://other.org/b/2",
+        ];
+        for url in urls {
+            redis.write_cache_and_clear(&service.cache_key(url), &StoredPage::new(url), 1, 600).await.unwrap();
+        }
+        service.record_failure("https://example.com/failed", FailureKind::Permanent, None).await.unwrap();
+        let cached = |url: &str| redis.ttl(&service.cache_key(url)).is_some();
+
+        assert_eq!(service.purge(&PurgeScope::Url(urls[0].to_string())).await.unwrap(), vec![urls[0]]);
+        assert!(!cached(urls[0]));
+        assert!(service.purge(&PurgeScope::Url(urls[0].to_string())).await.unwrap().is_empty());
+
+        let mut purged = service.purge(&PurgeScope::Host("example.com".to_string())).await.unwrap();
+        purged.sort();
+        assert_eq!(purged, vec![urls[2], urls[1]], "hash tags: {}", hash_tags);
+        assert!(cached(urls[3]) && cached(urls[4]));
+
+        let purged = service.purge(&PurgeScope::Prefix("https://other.org/b/".to_string())).await.unwrap();
+        assert_eq!(purged, vec![urls[4]]);
+        assert!(cached(urls[3]));
+
+        let failed = redis.get_failures(&[service.failure_key("https://example.com/failed")]).await.unwrap();
+        assert!(failed[0].is_some(), "purge must keep failure records");
+    }
+}
+
+#[tokio::test]
+async fn test_purge_by_pattern_matches_urls_as_globs() {
+    for hash_tags in [false, true] {
+        let redis = MemoryRedisAdapter::new(100);
+        let cfg = Config {
+            redis_hash_tags: hash_tags,
+            ..Config::default()
+        };
+        let service = LoadReducerService::new(redis.clone(), MockMongo::new(), MockCrawler::new(), cfg);
+        let urls = [
+            "https://example.com/a/amp",
+            "https://example.com/b/amp",
+            "https://example.com/b/full",
+            "https://example.com/a*/amp",
+        ];
+        for url in urls {
+            redis.write_cache_and_clear(&service.cache_key(url), &StoredPage::new(url), 1, 600).await.unwrap();
+        }
+        service.record_failure("https://example.com/c/amp", FailureKind::Permanent, None).await.unwrap();
+
+        let amp = PurgeScope::Pattern("https://example.com/?/amp".to_string());
+        let targets = service.purge_targets(&amp).await.unwrap();
+        assert_eq!(targets, vec![urls[0], urls[1]], "hash tags: {}", hash_tags);
+        assert_eq!(service.purge(&amp).await.unwrap(), targets);
+
+        // escaped glob syntax matches itself
+        let escaped = PurgeScope::Pattern("https://example.com/a\\*/*".to_string());
+        assert_eq!(service.purge(&escaped).await.unwrap(), vec![urls[3]]);
+
+        // failure records are never listed
+        let everything = service.purge_targets(&PurgeScope::Pattern("*".to_string())).await.unwrap();
+        assert_eq!(everything, vec![urls[2]]);
+    }
+}
+
+#[tokio::test]
+async fn test_forced_crawl_ignores_the_throttle_and_restarts_its_window() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let crawler = MockCrawler::new();
+    let service = LoadReducerService::new(redis.clone(), MockMongo::new(), crawler.clone(), Config::default())
+        .with_clock(clock.clone());
+    let missing = "https://example.com/missing".to_string();
+    let cached = "https://example.com/cached".to_string();
+    let failed = "https://example.com/failed".to_string();
+    redis.write_cache_and_clear(&service.cache_key(&cached), &StoredPage::new("old"), 1, 600).await.unwrap();
+    service.record_failure(&failed, FailureKind::Temporary, None).await.unwrap();
+
+    service.process(vec![missing.clone()]).await.unwrap();
+    clock.advance(Duration::from_secs(1));
+    let sent = service.force_crawl(&[missing.clone(), cached.clone(), failed.clone(), missing.clone()]).await.unwrap();
+    assert_eq!(sent, vec![missing.clone(), cached.clone(), failed.clone()]);
+    assert_eq!(crawler.sent.lock().unwrap().last().unwrap(), &sent);
+
+    // the crawler window now starts at the forced dispatch; cached data keeps its TTL
+    let now = clock.now_ms().to_string();
+    for url in [&missing, &cached, &failed] {
+        let hash = redis.hgetall(&service.cache_key(url)).await.unwrap();
+        assert_eq!(hash.get("last_crawler_send"), Some(&now), "{}", url);
+    }
+    assert_eq!(redis.ttl(&service.cache_key(&cached)), Some(Duration::from_secs(599)));
+    service.process(vec![missing.clone()]).await.unwrap();
+    assert_eq!(crawler.sent.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_inspection_follows_a_url_through_its_lifecycle() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let cfg = Config {
+        cache_ttl_sec: 600,
+        cache_soft_ttl_sec: 60,
+        mongo_prevent_ms: 1_000,
+        crawler_prevent_ms: 10_000,
+        ..Config::default()
+    };
+    let service = LoadReducerService::new(redis, mongo.clone(), crawler.clone(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/inspected";
+
+    let fresh = service.inspect(url).await.unwrap();
+    assert_eq!(fresh.cache_key, "rcs::https://example.com/inspected");
+    assert!(!fresh.cached && !fresh.stored && fresh.last_crawler_send.at.is_none());
+    assert_eq!(fresh.decision, Decision::SendToCrawler);
+    assert!(crawler.sent.lock().unwrap().is_empty(), "inspection must not dispatch");
+
+    service.process(vec![url.to_string()]).await.unwrap();
+    clock.advance(Duration::from_millis(4_000));
+    let queued = service.inspect(url).await.unwrap();
+    assert_eq!(queued.decision, Decision::WaitForCrawler);
+    assert!(queued.last_crawler_send.open && !queued.last_mongo_fetch.open);
+    assert_eq!(queued.last_crawler_send.remaining_ms, 6_000);
+    assert_eq!(queued.last_mongo_fetch.at, Some(1_700_000_000_000));
+
+    mongo.data.lock().unwrap().insert(url.to_string(), StoredPage::new("crawled"));
+    let stored = service.inspect(url).await.unwrap();
+    assert!(stored.stored && !stored.cached);
+    assert_eq!(stored.decision, Decision::ServeFromStorage { recrawl: false });
+
+    service.process(vec![url.to_string()]).await.unwrap();
+    clock.advance(Duration::from_secs(61));
+    let stale = service.inspect(url).await.unwrap();
+    assert!(stale.cached && stale.stale);
+    assert_eq!(stale.cache_ttl_ms, Some(539_000));
+    assert_eq!(stale.decision, Decision::ServeStale { refresh: true });
+
+    let failed = "https://example.com/inspected-failed";
+    service.record_failure(failed, FailureKind::Permanent, None).await.unwrap();
+    let failure = service.inspect(failed).await.unwrap();
+    assert_eq!(failure.decision, Decision::ReportFailure);
+    assert!(failure.failure.is_some());
+}
+
+#[tokio::test]
+async fn test_warm_caches_stored_pages_and_dry_purge_changes_nothing() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let mongo = MockMongo::new();
+    let crawler = MockCrawler::new();
+    let service = LoadReducerService::new(redis.clone(), mongo.clone(), crawler.clone(), Config::default())
+        .with_clock(clock.clone());
+    let stored = "https://example.com/stored".to_string();
+    let missing = "https://example.com/missing".to_string();
+    mongo.data.lock().unwrap().insert(stored.clone(), StoredPage::new("page"));
+
+    let cached = service.warm(&[stored.clone(), missing.clone(), stored.clone()]).await.unwrap();
+    assert_eq!(cached, vec![stored.clone()]);
+    assert!(redis.ttl(&service.cache_key(&stored)).is_some());
+    assert!(redis.ttl(&service.cache_key(&missing)).is_none());
+    assert!(crawler.sent.lock().unwrap().is_empty(), "warming must not dispatch");
+    assert!(service.inspect(&stored).await.unwrap().cached);
+
+    let targets = service.purge_targets(&PurgeScope::Host("example.com".to_string())).await.unwrap();
+    assert_eq!(targets, vec![stored.clone()]);
+    assert!(redis.ttl(&service.cache_key(&stored)).is_some(), "listing targets must not purge");
+}
+
+#[tokio::test]
+async fn test_throttle_state_reports_windows_and_failures() {
+    let clock = ManualClock::new(1_700_000_000_000);
+    let redis = MemoryRedisAdapter::new(100).with_clock(clock.clone());
+    let cfg = Config {
+        mongo_prevent_ms: 1_000,
+        crawler_prevent_ms: 10_000,
+        ..Config::default()
+    };
+    let service =
+        LoadReducerService::new(redis, MockMongo::new(), MockCrawler::new(), cfg).with_clock(clock.clone());
+    let url = "https://example.com/throttled";
+
+    let unseen = service.throttle_state(url).await.unwrap();
+    assert!(unseen.last_mongo_fetch.at.is_none() && unseen.last_crawler_send.at.is_none());
+
+    service.process(vec![url.to_string()]).await.unwrap();
+    clock.advance(Duration::from_millis(2_500));
+    let state = service.throttle_state(url).await.unwrap();
+    assert!(!state.last_mongo_fetch.open);
+    assert!(state.last_crawler_send.open);
+    assert_eq!(state.last_crawler_send.remaining_ms, 7_500);
+    assert!(state.failure.is_none());
+
+    service.record_failure(url, FailureKind::Temporary, Some("timeout".to_string())).await.unwrap();
+    let failed = service.throttle_state(url).await.unwrap();
+    assert_eq!(failed.failure.map(|f| f.kind), Some(FailureKind::Temporary));
+}
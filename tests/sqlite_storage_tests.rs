@@ -0,0 +1,75 @@
+use groove_throttle::adapters::sqlite_adapter::SqliteAdapter;
+use groove_throttle::config::{Config, StorageMode};
+use groove_throttle::domain::{PageMeta, StoredPage};
+use groove_throttle::ports::MongoPort;
+use serde_json::json;
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn test_saved_pages_are_found_with_their_metadata() {
+    let store = SqliteAdapter::in_memory().unwrap();
+    let meta = PageMeta {
+        status_code: Some(200),
+        content_type: Some("text/html".to_string()),
+        final_url: Some("https://a.example/home".to_string()),
+        crawled_at: Some(1_700_000_000_000),
+        etag: Some("\"v1\"".to_string()),
+    };
+    let pages = HashMap::from([
+        ("https://a.example/".to_string(), StoredPage::new("<html>a</html>").with_meta(meta)),
+        ("https://b.example/".to_string(), StoredPage::new(json!({"title": "b", "links": 3}))),
+    ]);
+    store.save_pages(&pages).await.unwrap();
+
+    let urls = vec![
+        "https://a.example/".to_string(),
+        "https://missing.example/".to_string(),
+        "https://b.example/".to_string(),
+        "https://a.example/".to_string(),
+    ];
+    let found = store.find_by_urls(&urls).await.unwrap();
+    assert_eq!(found, pages);
+    store.ping().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_saving_replaces_the_stored_page() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pages.sqlite");
+    let url = "https://a.example/".to_string();
+
+    let store = SqliteAdapter::open(&path).unwrap();
+    let first = StoredPage::new("old").with_meta(PageMeta { etag: Some("e1".to_string()), ..PageMeta::default() });
+    store.save_pages(&HashMap::from([(url.clone(), first)])).await.unwrap();
+    store.save_pages(&HashMap::from([(url.clone(), StoredPage::new("new").crawled_at(5))])).await.unwrap();
+    drop(store);
+
+    // the schema is kept on reopen and only the latest save is stored
+    let reopened = SqliteAdapter::open(&path).unwrap();
+    let found = reopened.find_by_urls(std::slice::from_ref(&url)).await.unwrap();
+    assert_eq!(found[&url], StoredPage::new("new").crawled_at(5));
+
+    // lookups larger than one IN query
+    let many: Vec<String> = (0..1_200).map(|i| format!("https://x.example/{}", i)).chain([url.clone()]).collect();
+    assert_eq!(reopened.find_by_urls(&many).await.unwrap().len(), 1);
+}
+
+#[test]
+fn test_storage_selected_from_env() {
+    let lookup = |name: &str| match name {
+        "STORAGE" => Some("sqlite".to_string()),
+        "SQLITE_PATH" => Some("/var/lib/groove/pages.db".to_string()),
+        _ => None,
+    };
+    let config = Config::from_sources(None, lookup).unwrap();
+    assert_eq!(config.storage, StorageMode::Sqlite);
+    assert_eq!(config.sqlite_path, "/var/lib/groove/pages.db");
+    assert_eq!(Config::default().storage, StorageMode::Mongo);
+
+    let bad = Config::from_sources(None, |name| match name {
+        "STORAGE" => Some("sqlite".to_string()),
+        "SQLITE_PATH" => Some(" ".to_string()),
+        _ => None,
+    });
+    assert!(bad.is_err());
+}